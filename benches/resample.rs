@@ -0,0 +1,32 @@
+//! Throughput of the resampler on typical microphone rates.
+//!
+//! Run with: `cargo bench --bench resample`
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use whisp::audio::resample;
+
+/// Seconds of audio resampled per run.
+const AUDIO_SECS: u32 = 60;
+
+fn main() {
+    for from_rate in [48000, 44100, 8000] {
+        let samples: Vec<f32> = (0..from_rate * AUDIO_SECS)
+            .map(|i| (i as f32 * 0.05).sin() * 0.5)
+            .collect();
+
+        let mut runs = 0;
+        let start = Instant::now();
+        while runs < 3 || start.elapsed() < Duration::from_secs(2) {
+            black_box(resample(black_box(&samples), from_rate, 16000));
+            runs += 1;
+        }
+        let per_run = start.elapsed() / runs;
+
+        println!(
+            "{from_rate} Hz -> 16000 Hz: {per_run:?} per {AUDIO_SECS}s of audio ({:.0}x realtime)",
+            AUDIO_SECS as f64 / per_run.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,273 @@
+//! Level and speech-content analysis for recordings.
+//!
+//! Speech detection is energy based: audio is split into short frames and a
+//! frame counts as speech when it is clearly above the recording's noise
+//! floor, crosses zero as rarely as voiced speech does, and belongs to a run
+//! long enough for a syllable. The last two keep keyboard clicks and other
+//! broadband transients from counting. This is cheap and good enough to tell
+//! a dictation from a silent or accidental recording, not a replacement for
+//! a real VAD.
+
+use std::time::Duration;
+
+/// Length of an analysis frame in seconds.
+const FRAME_SECS: f64 = 0.02;
+
+/// Samples at or above this magnitude count as clipped.
+const CLIP_LEVEL: f32 = 0.999;
+
+/// Frames quieter than this are never speech, in dBFS.
+const SILENCE_FLOOR_DBFS: f32 = -60.0;
+
+/// Upper bound on the estimated noise floor, in dBFS.
+///
+/// Keeps recordings without pauses from treating their own speech as noise.
+const MAX_NOISE_FLOOR_DBFS: f32 = -50.0;
+
+/// How far above the noise floor a frame must be to count as speech, in dB.
+const SPEECH_MARGIN_DB: f32 = 10.0;
+
+/// Percentile of frame levels used as the noise floor estimate.
+const NOISE_PERCENTILE: f64 = 0.1;
+
+/// Zero crossings per second above which a frame is noise, not speech.
+///
+/// Voiced speech crosses zero a few hundred to a few thousand times a
+/// second, clicks, hiss and other broadband noise far more often.
+const MAX_SPEECH_CROSSING_RATE: f32 = 5000.0;
+
+/// Shortest run of speech frames that counts, in seconds.
+///
+/// A key click rings for a few tens of milliseconds, a syllable lasts
+/// longer.
+const MIN_SPEECH_RUN_SECS: f64 = 0.1;
+
+/// Summary of a recording's levels and speech content.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AudioAnalysis {
+    /// Largest absolute sample value (0.0 to 1.0)
+    pub peak: f32,
+    /// Root mean square of all samples (0.0 to 1.0)
+    pub rms: f32,
+    /// Fraction of the recording detected as speech (0.0 to 1.0)
+    pub speech_ratio: f32,
+    /// Percentage of samples at or near full scale (0.0 to 100.0)
+    pub clipping_percent: f32,
+    /// Silence before the first speech frame
+    ///
+    /// Equal to the full duration if no speech was detected.
+    pub leading_silence: Duration,
+    /// Silence after the last speech frame
+    ///
+    /// Equal to the full duration if no speech was detected.
+    pub trailing_silence: Duration,
+    /// Total duration that was analyzed
+    pub duration: Duration,
+}
+
+impl AudioAnalysis {
+    /// Duration of the detected speech.
+    pub fn speech_duration(&self) -> Duration {
+        self.duration.mul_f32(self.speech_ratio)
+    }
+
+    /// Peak level in dBFS.
+    pub fn peak_dbfs(&self) -> f32 {
+        20.0 * self.peak.log10()
+    }
+
+    /// RMS level in dBFS.
+    pub fn rms_dbfs(&self) -> f32 {
+        20.0 * self.rms.log10()
+    }
+}
+
+/// Analyze interleaved samples.
+pub fn analyze(samples: &[f32], channels: u16, sample_rate: u32) -> AudioAnalysis {
+    let mut analyzer = Analyzer::new(channels, sample_rate);
+    analyzer.push(samples);
+    analyzer.finish()
+}
+
+/// Incremental [`analyze`] over a recording fed in blocks.
+///
+/// Only the level and zero-crossing rate of each frame are kept, so a long
+/// recording never has to be decoded into memory as a whole.
+#[derive(Debug, Clone)]
+pub struct Analyzer {
+    channels: usize,
+    sample_rate: u32,
+    /// Analysis frame length in sample frames
+    frame_len: usize,
+    samples: u64,
+    peak: f32,
+    sum_squares: f64,
+    clipped: u64,
+    /// Sum and count of the channels seen of the current sample frame
+    mix: f32,
+    mixed: usize,
+    /// Sum of squares and count of the mixed samples in the current
+    /// analysis frame
+    frame_squares: f32,
+    framed: usize,
+    /// Last mixed sample, and sign changes of the mix in the current
+    /// analysis frame
+    last_mix: f32,
+    crossings: usize,
+    /// Per-frame level of the channel mix, in dBFS
+    levels: Vec<f32>,
+    /// Per-frame zero crossings of the channel mix, per second
+    crossing_rates: Vec<f32>,
+}
+
+impl Analyzer {
+    /// Create an analyzer for interleaved samples of the given format.
+    pub fn new(channels: u16, sample_rate: u32) -> Self {
+        Self {
+            channels: channels.max(1) as usize,
+            sample_rate,
+            frame_len: ((sample_rate as f64 * FRAME_SECS) as usize).max(1),
+            samples: 0,
+            peak: 0.0,
+            sum_squares: 0.0,
+            clipped: 0,
+            mix: 0.0,
+            mixed: 0,
+            frame_squares: 0.0,
+            framed: 0,
+            last_mix: 0.0,
+            crossings: 0,
+            levels: Vec::new(),
+            crossing_rates: Vec::new(),
+        }
+    }
+
+    /// Feed the next block of interleaved samples.
+    ///
+    /// Blocks don't have to align with channels or frames.
+    pub fn push(&mut self, samples: &[f32]) {
+        for &sample in samples {
+            let level = sample.abs();
+            self.peak = self.peak.max(level);
+            self.sum_squares += (sample as f64) * (sample as f64);
+            if level >= CLIP_LEVEL {
+                self.clipped += 1;
+            }
+
+            self.mix += sample;
+            self.mixed += 1;
+            if self.mixed == self.channels {
+                let mix = self.mix / self.channels as f32;
+                self.frame_squares += mix * mix;
+                self.framed += 1;
+                if (mix < 0.0) != (self.last_mix < 0.0) {
+                    self.crossings += 1;
+                }
+                self.last_mix = mix;
+                self.mix = 0.0;
+                self.mixed = 0;
+            }
+            if self.framed == self.frame_len {
+                self.end_frame();
+            }
+        }
+        self.samples += samples.len() as u64;
+    }
+
+    /// Record the level and zero-crossing rate of the current analysis
+    /// frame and start the next.
+    fn end_frame(&mut self) {
+        let framed = self.framed.max(1) as f32;
+        let mean_square = self.frame_squares / framed;
+        self.levels.push(10.0 * mean_square.log10());
+        self.crossing_rates
+            .push(self.crossings as f32 / framed * self.sample_rate as f32);
+        self.frame_squares = 0.0;
+        self.framed = 0;
+        self.crossings = 0;
+    }
+
+    /// Summarize everything fed so far.
+    pub fn finish(mut self) -> AudioAnalysis {
+        if self.framed > 0 {
+            self.end_frame();
+        }
+
+        let frames = self.samples / self.channels as u64;
+        let duration = Duration::from_secs_f64(frames as f64 / self.sample_rate as f64);
+        let (rms, clipping_percent) = if self.samples == 0 {
+            (0.0, 0.0)
+        } else {
+            (
+                (self.sum_squares / self.samples as f64).sqrt() as f32,
+                self.clipped as f32 / self.samples as f32 * 100.0,
+            )
+        };
+
+        let threshold = speech_threshold(&self.levels);
+        let voiced: Vec<bool> = self
+            .levels
+            .iter()
+            .zip(&self.crossing_rates)
+            .map(|(&level, &rate)| level > threshold && rate <= MAX_SPEECH_CROSSING_RATE)
+            .collect();
+        let min_run = (MIN_SPEECH_RUN_SECS / FRAME_SECS).round() as usize;
+        let speech = sustained(&voiced, min_run);
+        let speech_frames = speech.iter().filter(|&&s| s).count();
+        let frame_duration = Duration::from_secs_f64(FRAME_SECS);
+
+        let (leading_silence, trailing_silence) = match (
+            speech.iter().position(|&s| s),
+            speech.iter().rposition(|&s| s),
+        ) {
+            (Some(first), Some(last)) => (
+                frame_duration * first as u32,
+                duration.saturating_sub(frame_duration * (last as u32 + 1)),
+            ),
+            _ => (duration, duration),
+        };
+
+        AudioAnalysis {
+            peak: self.peak,
+            rms,
+            speech_ratio: if speech.is_empty() {
+                0.0
+            } else {
+                speech_frames as f32 / speech.len() as f32
+            },
+            clipping_percent,
+            leading_silence,
+            trailing_silence,
+            duration,
+        }
+    }
+}
+
+/// Keep only the runs of at least `min_run` set frames.
+fn sustained(frames: &[bool], min_run: usize) -> Vec<bool> {
+    let mut result = vec![false; frames.len()];
+    let mut start = 0;
+    while start < frames.len() {
+        let len = frames[start..]
+            .iter()
+            .take_while(|&&f| f == frames[start])
+            .count();
+        if frames[start] && len >= min_run {
+            result[start..start + len].fill(true);
+        }
+        start += len;
+    }
+    result
+}
+
+/// Level a frame must exceed to count as speech.
+fn speech_threshold(levels: &[f32]) -> f32 {
+    let mut sorted = levels.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    let noise_floor = sorted
+        .get((sorted.len() as f64 * NOISE_PERCENTILE) as usize)
+        .copied()
+        .unwrap_or(f32::NEG_INFINITY)
+        .min(MAX_NOISE_FLOOR_DBFS);
+    (noise_floor + SPEECH_MARGIN_DB).max(SILENCE_FLOOR_DBFS)
+}
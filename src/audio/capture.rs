@@ -0,0 +1,341 @@
+//! Lock-free capture path.
+//!
+//! The cpal callback runs on a real-time audio thread and must never block.
+//! It only pushes samples into a single-producer single-consumer ring buffer;
+//! a dedicated writer thread drains them into the WAV encoder. Samples that
+//! don't fit in the ring buffer are dropped and counted, so gaps show up on
+//! the finished [`Recording`](super::Recording) instead of disappearing.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use hound::{WavSpec, WavWriter};
+use rtrb::{Consumer, Producer, RingBuffer};
+use tracing::warn;
+
+use super::gain::AutoGain;
+use super::journal::JournalWriter;
+use super::storage::{SpillConfig, SpillWriter, StoredAudio};
+use super::{RecorderError, Result};
+
+/// Seconds of audio the ring buffer can hold before samples are dropped.
+const RING_BUFFER_SECS: usize = 2;
+
+/// How long the writer thread sleeps when the ring buffer is empty.
+const DRAIN_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Counters shared between the audio callback and the writer.
+#[derive(Debug, Default)]
+struct CaptureStats {
+    /// Samples that didn't fit in the ring buffer
+    dropped_samples: AtomicU64,
+    /// Callbacks that had to drop at least one sample
+    overruns: AtomicU64,
+}
+
+/// Audio-thread side of the capture path.
+pub(crate) struct CaptureProducer {
+    producer: Producer<f32>,
+    stats: Arc<CaptureStats>,
+    paused: Arc<AtomicBool>,
+}
+
+impl CaptureProducer {
+    /// Whether the capture is paused and incoming samples should be skipped.
+    pub(crate) fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    /// Push samples without blocking, counting any that don't fit.
+    pub(crate) fn push(&mut self, data: &[f32]) {
+        let n = self.producer.slots().min(data.len());
+        if let Ok(chunk) = self.producer.write_chunk_uninit(n) {
+            chunk.fill_from_iter(data.iter().copied());
+        }
+
+        let dropped = data.len() - n;
+        if dropped > 0 {
+            self.stats
+                .dropped_samples
+                .fetch_add(dropped as u64, Ordering::Relaxed);
+            self.stats.overruns.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Everything the writer produced once capture stops.
+pub(crate) struct CaptureOutput {
+    /// Finalized WAV data
+    pub audio: StoredAudio,
+    /// Final gain applied by automatic gain control, in dB
+    pub auto_gain_db: Option<f32>,
+    /// Journal of the recording, if journaling worked throughout
+    pub journal_path: Option<PathBuf>,
+    /// Samples dropped because the ring buffer was full
+    pub dropped_samples: u64,
+    /// Number of audio callbacks that dropped samples
+    pub overruns: u64,
+}
+
+/// What the writer thread hands back when it's done.
+struct WriterOutput {
+    audio: StoredAudio,
+    auto_gain_db: Option<f32>,
+    journal_path: Option<PathBuf>,
+}
+
+type WriterResult = Result<WriterOutput>;
+
+/// Writer-thread side of the capture path.
+pub(crate) struct CaptureWriter {
+    stop: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+    thread: Option<JoinHandle<WriterResult>>,
+    stats: Arc<CaptureStats>,
+}
+
+impl CaptureWriter {
+    /// Pause or resume capturing samples.
+    ///
+    /// While paused the audio callback skips samples, so the recording
+    /// continues seamlessly on resume.
+    pub(crate) fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+
+    /// Whether capturing is paused.
+    pub(crate) fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    /// Stop the writer once the ring buffer is drained and finalize the WAV.
+    ///
+    /// The audio stream should be paused first so no more samples arrive.
+    pub(crate) fn finish(mut self) -> Result<CaptureOutput> {
+        self.stop.store(true, Ordering::Release);
+        let thread = self
+            .thread
+            .take()
+            .expect("writer thread is only joined once");
+        let output = thread.join().map_err(|_| {
+            RecorderError::Anyhow(anyhow::anyhow!("audio writer thread panicked"))
+        })??;
+
+        Ok(CaptureOutput {
+            audio: output.audio,
+            auto_gain_db: output.auto_gain_db,
+            journal_path: output.journal_path,
+            dropped_samples: self.stats.dropped_samples.load(Ordering::Relaxed),
+            overruns: self.stats.overruns.load(Ordering::Relaxed),
+        })
+    }
+}
+
+impl Drop for CaptureWriter {
+    fn drop(&mut self) {
+        // Let the thread exit if the capture is abandoned without finishing
+        self.stop.store(true, Ordering::Release);
+    }
+}
+
+/// Set up the ring buffer and spawn the writer thread.
+///
+/// Failing to create the journal doesn't stop the recording, it just isn't
+/// crash-safe.
+pub(crate) fn start(
+    spec: WavSpec,
+    auto_gain: Option<AutoGain>,
+    spill: Option<SpillConfig>,
+    journal_dir: Option<&Path>,
+) -> Result<(CaptureProducer, CaptureWriter)> {
+    let journal = journal_dir.and_then(|dir| {
+        JournalWriter::create(dir, spec)
+            .inspect_err(|e| warn!("Failed to create recording journal: {:#}", e))
+            .ok()
+    });
+
+    let capacity = spec.sample_rate as usize * spec.channels as usize * RING_BUFFER_SECS;
+    let (producer, consumer) = RingBuffer::new(capacity);
+    let stats = Arc::new(CaptureStats::default());
+    let stop = Arc::new(AtomicBool::new(false));
+    let paused = Arc::new(AtomicBool::new(false));
+
+    let thread_stop = stop.clone();
+    let thread = thread::Builder::new()
+        .name("whisp-audio-writer".to_string())
+        .spawn(move || drain(consumer, spec, auto_gain, spill, journal, &thread_stop))
+        .map_err(|e| RecorderError::Anyhow(e.into()))?;
+
+    Ok((
+        CaptureProducer {
+            producer,
+            stats: stats.clone(),
+            paused: paused.clone(),
+        },
+        CaptureWriter {
+            stop,
+            paused,
+            thread: Some(thread),
+            stats,
+        },
+    ))
+}
+
+/// Writer thread body: move samples from the ring buffer into the encoder.
+fn drain(
+    mut consumer: Consumer<f32>,
+    spec: WavSpec,
+    mut auto_gain: Option<AutoGain>,
+    spill: Option<SpillConfig>,
+    mut journal: Option<JournalWriter>,
+    stop: &AtomicBool,
+) -> WriterResult {
+    let mut buffer = SpillWriter::new(spill);
+    let mut writer =
+        WavWriter::new(&mut buffer, spec).map_err(|e| RecorderError::Anyhow(e.into()))?;
+
+    loop {
+        // Check before reading so samples pushed before the stop are drained
+        let stopping = stop.load(Ordering::Acquire) || consumer.is_abandoned();
+
+        let available = consumer.slots();
+        if available == 0 {
+            if stopping {
+                break;
+            }
+            thread::sleep(DRAIN_INTERVAL);
+            continue;
+        }
+
+        let chunk = consumer
+            .read_chunk(available)
+            .expect("chunk within available slots");
+        let (first, second) = chunk.as_slices();
+        for &sample in first.iter().chain(second) {
+            let sample = match auto_gain.as_mut() {
+                Some(agc) => agc.process(sample),
+                None => sample,
+            };
+            writer
+                .write_sample(sample)
+                .map_err(|e| RecorderError::Anyhow(e.into()))?;
+            if let Some(j) = journal.as_mut()
+                && let Err(e) = j.write_sample(sample)
+            {
+                warn!("Recording journal failed, continuing without it: {:#}", e);
+                journal = None;
+            }
+        }
+        chunk.commit_all();
+
+        if let Some(j) = journal.as_mut()
+            && let Err(e) = j.checkpoint_if_due()
+        {
+            warn!("Recording journal failed, continuing without it: {:#}", e);
+            journal = None;
+        }
+    }
+
+    writer
+        .finalize()
+        .map_err(|e| RecorderError::Anyhow(anyhow::anyhow!("Failed to finalize writer: {}", e)))?;
+
+    let audio = buffer
+        .finish()
+        .map_err(|e| RecorderError::Anyhow(e.into()))?;
+    let journal_path = journal.and_then(|j| {
+        j.finish()
+            .inspect_err(|e| warn!("Failed to finalize recording journal: {:#}", e))
+            .ok()
+    });
+
+    Ok(WriterOutput {
+        audio,
+        auto_gain_db: auto_gain.map(|agc| agc.gain_db()),
+        journal_path,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(sample_rate: u32) -> WavSpec {
+        WavSpec {
+            channels: 1,
+            sample_rate,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        }
+    }
+
+    fn decode(data: &[u8]) -> Vec<f32> {
+        hound::WavReader::new(std::io::Cursor::new(data))
+            .unwrap()
+            .into_samples::<f32>()
+            .collect::<std::result::Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_capture_round_trip() {
+        let (mut producer, writer) = start(spec(16000), None, None, None).unwrap();
+        let input: Vec<f32> = (0..4000).map(|i| i as f32 / 4000.0).collect();
+        for chunk in input.chunks(256) {
+            producer.push(chunk);
+        }
+
+        let output = writer.finish().unwrap();
+
+        assert_eq!(decode(&output.audio.data), input);
+        assert!(output.journal_path.is_none());
+        assert_eq!(output.dropped_samples, 0);
+        assert_eq!(output.overruns, 0);
+        assert!(output.auto_gain_db.is_none());
+    }
+
+    #[test]
+    fn test_long_capture_spills_to_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let spill = SpillConfig::new(16 * 1024, dir.path());
+        let (mut producer, writer) = start(spec(16000), None, Some(spill), None).unwrap();
+        let input: Vec<f32> = (0..16000).map(|i| (i % 100) as f32 / 100.0).collect();
+        for chunk in input.chunks(512) {
+            producer.push(chunk);
+        }
+
+        let output = writer.finish().unwrap();
+
+        assert!(output.audio.path.unwrap().exists());
+        assert_eq!(decode(&output.audio.data), input);
+    }
+
+    #[test]
+    fn test_capture_writes_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut producer, writer) = start(spec(16000), None, None, Some(dir.path())).unwrap();
+        let input: Vec<f32> = (0..1000).map(|i| i as f32 / 1000.0).collect();
+        producer.push(&input);
+
+        let output = writer.finish().unwrap();
+
+        let journal = std::fs::read(output.journal_path.unwrap()).unwrap();
+        assert_eq!(decode(&journal), input);
+    }
+
+    #[test]
+    fn test_overrun_is_counted() {
+        // One sample per second gives a two-sample ring buffer
+        let (mut producer, writer) = start(spec(1), None, None, None).unwrap();
+        producer.push(&[0.1, 0.2, 0.3, 0.4, 0.5]);
+
+        let output = writer.finish().unwrap();
+
+        assert_eq!(output.overruns, 1);
+        assert_eq!(output.dropped_samples, 3);
+        assert_eq!(decode(&output.audio.data), vec![0.1, 0.2]);
+    }
+}
@@ -0,0 +1,480 @@
+//! Gain staging for recordings.
+//!
+//! Quiet laptop microphones can produce recordings peaking around -40 dBFS,
+//! which both transcription backends handle poorly. This module provides a
+//! post-capture normalization pass (peak or integrated loudness, followed by
+//! a limiter) and a real-time automatic gain control for the capture path.
+
+use crate::core::{Config, Normalization};
+
+/// Ceiling enforced by the limiter, -1 dBFS.
+const LIMITER_CEILING_DBFS: f32 = -1.0;
+
+/// Limiter release time in seconds.
+const LIMITER_RELEASE_SECS: f32 = 0.05;
+
+/// RMS level the automatic gain control steers towards, in dBFS.
+const AGC_TARGET_DBFS: f32 = -20.0;
+
+/// Window used to track the input level for automatic gain control.
+const AGC_LEVEL_SECS: f32 = 0.05;
+
+/// How quickly the gain drops when the input gets louder.
+const AGC_ATTACK_SECS: f32 = 0.05;
+
+/// How quickly the gain recovers when the input gets quieter.
+const AGC_RELEASE_SECS: f32 = 2.0;
+
+/// Input level below which the gain is held instead of raised, in dBFS.
+///
+/// Prevents pumping up the noise floor during pauses.
+const AGC_GATE_DBFS: f32 = -65.0;
+
+/// Duration of a loudness measurement block (BS.1770).
+const LOUDNESS_BLOCK_SECS: f32 = 0.4;
+
+/// Number of 100ms steps per measurement block (75% overlap).
+const LOUDNESS_STEPS_PER_BLOCK: usize = 4;
+
+/// Absolute gate for loudness measurement, in LUFS.
+const LOUDNESS_ABSOLUTE_GATE: f64 = -70.0;
+
+/// Relative gate for loudness measurement, in LU below the ungated level.
+const LOUDNESS_RELATIVE_GATE: f64 = -10.0;
+
+/// Settings for normalizing a finished recording.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NormalizeConfig {
+    /// How the recording level is measured
+    pub mode: Normalization,
+    /// Target level, dBFS for peak and LUFS for loudness
+    pub target: f32,
+    /// Upper bound on the applied gain, in dB
+    pub max_gain_db: f32,
+}
+
+impl NormalizeConfig {
+    /// Create normalization settings with the mode's default target.
+    pub fn new(mode: Normalization) -> Self {
+        Self {
+            mode,
+            target: mode.default_target(),
+            max_gain_db: Config::default().max_gain_db,
+        }
+    }
+
+    /// Build normalization settings from the app config.
+    ///
+    /// Returns `None` when normalization is turned off.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        if config.normalize == Normalization::Off {
+            return None;
+        }
+        Some(Self {
+            mode: config.normalize,
+            target: config.normalize_target(),
+            max_gain_db: config.max_gain_db,
+        })
+    }
+}
+
+/// Gain applied by a normalization pass.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AppliedGain {
+    /// Level measured before normalization (dBFS or LUFS depending on mode)
+    pub measured: f32,
+    /// Gain that was applied, in dB
+    pub gain_db: f32,
+}
+
+/// Normalize interleaved samples in place.
+///
+/// Returns `None` if normalization is off or the level can't be measured
+/// (silence, or too short for a loudness measurement).
+pub fn normalize(
+    samples: &mut [f32],
+    channels: u16,
+    sample_rate: u32,
+    config: &NormalizeConfig,
+) -> Option<AppliedGain> {
+    let measured = match config.mode {
+        Normalization::Off => return None,
+        Normalization::Peak => peak_dbfs(samples)?,
+        Normalization::Loudness => integrated_loudness(samples, channels, sample_rate)?,
+    };
+
+    let gain_db = (config.target - measured).min(config.max_gain_db);
+    let gain = db_to_linear(gain_db);
+
+    match config.mode {
+        // Peak normalization can't overshoot the target, no limiting needed
+        Normalization::Peak => samples.iter_mut().for_each(|s| *s *= gain),
+        _ => {
+            let mut limiter = Limiter::new(sample_rate, channels);
+            samples
+                .iter_mut()
+                .for_each(|s| *s = limiter.process(*s * gain));
+        }
+    }
+
+    Some(AppliedGain { measured, gain_db })
+}
+
+/// Peak level of the samples in dBFS, or `None` for digital silence.
+pub fn peak_dbfs(samples: &[f32]) -> Option<f32> {
+    let peak = samples.iter().fold(0.0f32, |acc, s| acc.max(s.abs()));
+    (peak > 0.0).then(|| linear_to_db(peak))
+}
+
+/// Integrated loudness of interleaved samples in LUFS (ITU-R BS.1770).
+///
+/// All channels are weighted equally. Returns `None` if the recording is
+/// shorter than one 400ms measurement block or entirely below the gate.
+pub fn integrated_loudness(samples: &[f32], channels: u16, sample_rate: u32) -> Option<f32> {
+    let channels = channels.max(1) as usize;
+    let step =
+        (sample_rate as f32 * LOUDNESS_BLOCK_SECS / LOUDNESS_STEPS_PER_BLOCK as f32) as usize;
+    if step == 0 {
+        return None;
+    }
+
+    // Sum of K-weighted squares per 100ms step, across all channels
+    let mut filters = vec![KWeighting::new(sample_rate); channels];
+    let mut steps = Vec::with_capacity(samples.len() / channels / step + 1);
+    let mut energy = 0.0f64;
+    for (i, frame) in samples.chunks_exact(channels).enumerate() {
+        for (filter, &sample) in filters.iter_mut().zip(frame) {
+            let weighted = filter.process(sample as f64);
+            energy += weighted * weighted;
+        }
+        if (i + 1) % step == 0 {
+            steps.push(energy);
+            energy = 0.0;
+        }
+    }
+
+    let block_len = (step * LOUDNESS_STEPS_PER_BLOCK) as f64;
+    let blocks: Vec<f64> = steps
+        .windows(LOUDNESS_STEPS_PER_BLOCK)
+        .map(|w| w.iter().sum::<f64>() / block_len)
+        .filter(|&z| block_loudness(z) > LOUDNESS_ABSOLUTE_GATE)
+        .collect();
+    if blocks.is_empty() {
+        return None;
+    }
+
+    let relative_gate =
+        block_loudness(blocks.iter().sum::<f64>() / blocks.len() as f64) + LOUDNESS_RELATIVE_GATE;
+    let gated: Vec<f64> = blocks
+        .into_iter()
+        .filter(|&z| block_loudness(z) > relative_gate)
+        .collect();
+    if gated.is_empty() {
+        return None;
+    }
+
+    Some(block_loudness(gated.iter().sum::<f64>() / gated.len() as f64) as f32)
+}
+
+fn block_loudness(mean_square: f64) -> f64 {
+    -0.691 + 10.0 * mean_square.log10()
+}
+
+fn db_to_linear(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+fn linear_to_db(gain: f32) -> f32 {
+    20.0 * gain.log10()
+}
+
+/// One-pole smoothing coefficient for a time constant at a sample rate.
+fn smoothing(secs: f32, rate: f32) -> f32 {
+    1.0 - (-1.0 / (secs * rate)).exp()
+}
+
+/// Settings for real-time automatic gain control during capture.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutoGainConfig {
+    /// RMS level the gain control steers towards, in dBFS
+    pub target_dbfs: f32,
+    /// Upper bound on the applied gain, in dB
+    pub max_gain_db: f32,
+}
+
+impl Default for AutoGainConfig {
+    fn default() -> Self {
+        Self {
+            target_dbfs: AGC_TARGET_DBFS,
+            max_gain_db: Config::default().max_gain_db,
+        }
+    }
+}
+
+impl AutoGainConfig {
+    /// Build AGC settings from the app config.
+    ///
+    /// Returns `None` when AGC is turned off.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        config.agc.then_some(Self {
+            target_dbfs: AGC_TARGET_DBFS,
+            max_gain_db: config.max_gain_db,
+        })
+    }
+}
+
+/// Real-time automatic gain control for interleaved samples.
+///
+/// Tracks the input level and smoothly steers the gain towards the target,
+/// dropping quickly on loud input and recovering slowly. The output passes
+/// through a limiter so boosted peaks never clip.
+pub(crate) struct AutoGain {
+    target: f32,
+    max_gain: f32,
+    gate: f32,
+    level_coef: f32,
+    attack: f32,
+    release: f32,
+    /// Mean-square level envelope
+    level: f32,
+    gain: f32,
+    limiter: Limiter,
+}
+
+impl AutoGain {
+    pub(crate) fn new(config: AutoGainConfig, sample_rate: u32, channels: u16) -> Self {
+        // Samples are interleaved, so time constants run at rate * channels
+        let rate = sample_rate as f32 * channels.max(1) as f32;
+        Self {
+            target: db_to_linear(config.target_dbfs),
+            max_gain: db_to_linear(config.max_gain_db),
+            gate: db_to_linear(AGC_GATE_DBFS),
+            level_coef: smoothing(AGC_LEVEL_SECS, rate),
+            attack: smoothing(AGC_ATTACK_SECS, rate),
+            release: smoothing(AGC_RELEASE_SECS, rate),
+            level: 0.0,
+            gain: 1.0,
+            limiter: Limiter::new(sample_rate, channels),
+        }
+    }
+
+    /// Apply the current gain to a sample and update the gain.
+    pub(crate) fn process(&mut self, sample: f32) -> f32 {
+        self.level += self.level_coef * (sample * sample - self.level);
+        let rms = self.level.sqrt();
+        if rms > self.gate {
+            let desired = (self.target / rms).min(self.max_gain);
+            let coef = if desired < self.gain {
+                self.attack
+            } else {
+                self.release
+            };
+            self.gain += coef * (desired - self.gain);
+        }
+        self.limiter.process(sample * self.gain)
+    }
+
+    /// Current gain in dB.
+    pub(crate) fn gain_db(&self) -> f32 {
+        linear_to_db(self.gain)
+    }
+}
+
+/// Feed-forward peak limiter without lookahead.
+///
+/// Gain drops instantly to keep each sample under the ceiling and recovers
+/// with a short release, so the output never exceeds the ceiling.
+struct Limiter {
+    ceiling: f32,
+    release: f32,
+    gain: f32,
+}
+
+impl Limiter {
+    fn new(sample_rate: u32, channels: u16) -> Self {
+        let rate = sample_rate as f32 * channels.max(1) as f32;
+        Self {
+            ceiling: db_to_linear(LIMITER_CEILING_DBFS),
+            release: smoothing(LIMITER_RELEASE_SECS, rate),
+            gain: 1.0,
+        }
+    }
+
+    fn process(&mut self, sample: f32) -> f32 {
+        self.gain += self.release * (1.0 - self.gain);
+        let level = sample.abs();
+        if level * self.gain > self.ceiling {
+            self.gain = self.ceiling / level;
+        }
+        sample * self.gain
+    }
+}
+
+/// BS.1770 K-weighting: a high-shelf pre-filter followed by a high-pass.
+#[derive(Debug, Clone, Copy)]
+struct KWeighting {
+    shelf: Biquad,
+    high_pass: Biquad,
+}
+
+impl KWeighting {
+    fn new(sample_rate: u32) -> Self {
+        let fs = sample_rate as f64;
+
+        // Coefficients derived for arbitrary sample rates (as in libebur128)
+        let gain_db = 3.999_843_853_973_347;
+        let f0 = 1_681.974_450_955_533;
+        let q = 0.707_175_236_955_419_6;
+        let k = (std::f64::consts::PI * f0 / fs).tan();
+        let vh = 10f64.powf(gain_db / 20.0);
+        let vb = vh.powf(0.499_666_774_154_541_6);
+        let a0 = 1.0 + k / q + k * k;
+        let shelf = Biquad::new(
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+            2.0 * (k * k - 1.0) / a0,
+            (1.0 - k / q + k * k) / a0,
+        );
+
+        let f0 = 38.135_470_876_024_44;
+        let q = 0.500_327_037_323_877_3;
+        let k = (std::f64::consts::PI * f0 / fs).tan();
+        let a0 = 1.0 + k / q + k * k;
+        let high_pass = Biquad::new(
+            1.0,
+            -2.0,
+            1.0,
+            2.0 * (k * k - 1.0) / a0,
+            (1.0 - k / q + k * k) / a0,
+        );
+
+        Self { shelf, high_pass }
+    }
+
+    fn process(&mut self, sample: f64) -> f64 {
+        self.high_pass.process(self.shelf.process(sample))
+    }
+}
+
+/// Second-order IIR filter (transposed direct form II).
+#[derive(Debug, Clone, Copy)]
+struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+    z1: f64,
+    z2: f64,
+}
+
+impl Biquad {
+    fn new(b0: f64, b1: f64, b2: f64, a1: f64, a2: f64) -> Self {
+        Self {
+            b0,
+            b1,
+            b2,
+            a1,
+            a2,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b0 * x + self.z1;
+        self.z1 = self.b1 * x - self.a1 * y + self.z2;
+        self.z2 = self.b2 * x - self.a2 * y;
+        y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(freq: f32, amplitude: f32, sample_rate: u32, secs: f32) -> Vec<f32> {
+        let len = (sample_rate as f32 * secs) as usize;
+        (0..len)
+            .map(|i| {
+                let t = i as f32 / sample_rate as f32;
+                amplitude * (2.0 * std::f32::consts::PI * freq * t).sin()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_peak_normalization_hits_target() {
+        let mut samples = sine(1000.0, 0.05, 16000, 1.0);
+        let config = NormalizeConfig::new(Normalization::Peak);
+
+        let applied = normalize(&mut samples, 1, 16000, &config).unwrap();
+
+        assert!((applied.measured - -26.02).abs() < 0.01);
+        assert!((peak_dbfs(&samples).unwrap() - config.target).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_normalization_respects_max_gain() {
+        let mut samples = sine(1000.0, 0.0001, 16000, 1.0);
+        let config = NormalizeConfig {
+            max_gain_db: 20.0,
+            ..NormalizeConfig::new(Normalization::Peak)
+        };
+
+        let applied = normalize(&mut samples, 1, 16000, &config).unwrap();
+
+        assert_eq!(applied.gain_db, 20.0);
+        assert!((peak_dbfs(&samples).unwrap() - -60.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_silence_is_left_alone() {
+        let mut samples = vec![0.0; 16000];
+        let config = NormalizeConfig::new(Normalization::Loudness);
+        assert!(normalize(&mut samples, 1, 16000, &config).is_none());
+        assert!(peak_dbfs(&samples).is_none());
+    }
+
+    #[test]
+    fn test_integrated_loudness_of_reference_tone() {
+        // A 997 Hz sine at -20 dBFS measures -23 LUFS per BS.1770
+        let samples = sine(997.0, 0.1, 48000, 5.0);
+        let loudness = integrated_loudness(&samples, 1, 48000).unwrap();
+        assert!((loudness - -23.0).abs() < 0.1, "loudness {loudness}");
+
+        // Identical stereo channels sum to +3 LU
+        let stereo: Vec<f32> = samples.iter().flat_map(|&s| [s, s]).collect();
+        let loudness = integrated_loudness(&stereo, 2, 48000).unwrap();
+        assert!((loudness - -19.99).abs() < 0.1, "loudness {loudness}");
+    }
+
+    #[test]
+    fn test_loudness_normalization_is_limited() {
+        let mut samples = sine(997.0, 0.01, 48000, 2.0);
+        let config = NormalizeConfig {
+            target: -6.0,
+            ..NormalizeConfig::new(Normalization::Loudness)
+        };
+
+        let applied = normalize(&mut samples, 1, 48000, &config).unwrap();
+
+        assert!(applied.gain_db > 20.0);
+        assert!(peak_dbfs(&samples).unwrap() <= LIMITER_CEILING_DBFS + 0.001);
+    }
+
+    #[test]
+    fn test_auto_gain_boosts_quiet_input() {
+        let input = sine(440.0, 0.005, 16000, 10.0);
+        let mut agc = AutoGain::new(AutoGainConfig::default(), 16000, 1);
+
+        let output: Vec<f32> = input.iter().map(|&s| agc.process(s)).collect();
+
+        // Last second should sit near the -20 dBFS RMS target
+        let tail = &output[output.len() - 16000..];
+        let rms = (tail.iter().map(|s| s * s).sum::<f32>() / tail.len() as f32).sqrt();
+        assert!((linear_to_db(rms) - AGC_TARGET_DBFS).abs() < 2.0);
+        assert!(agc.gain_db() > 20.0);
+    }
+}
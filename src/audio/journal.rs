@@ -0,0 +1,237 @@
+//! Crash-safe journal of in-progress recordings.
+//!
+//! While recording, the writer thread also streams audio to a WAV file in
+//! the journal directory and checkpoints it every second: the header is
+//! rewritten to cover all samples so far and the file is synced to disk. If
+//! whisp dies mid-dictation, everything up to the last checkpoint is still a
+//! valid WAV file. The journal is deleted once the recording is transcribed
+//! or discarded, so any journal left over on startup is an orphan that can
+//! be recovered with [`Recording::from_journal`](super::Recording::from_journal).
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Seek};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use hound::{WavReader, WavSpec, WavWriter};
+use tracing::warn;
+
+use crate::core::default_data_dir;
+
+/// How often the journal is checkpointed.
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// File name prefix of journal files.
+const JOURNAL_PREFIX: &str = "journal-";
+
+/// Returns the directory where recording journals are stored.
+pub fn journal_dir() -> Result<PathBuf> {
+    Ok(default_data_dir()?.join("journal"))
+}
+
+/// List journals in `dir` left behind by a previous run, oldest first.
+pub fn orphaned_journals(dir: &Path) -> Result<Vec<PathBuf>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).context("Failed to read journal directory"),
+    };
+
+    let own_suffix = format!("-{}.wav", std::process::id());
+    let mut journals: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| {
+                    name.starts_with(JOURNAL_PREFIX) && name.ends_with(".wav")
+                        // Journals of this process are still being written
+                        && !name.ends_with(&own_suffix)
+                })
+        })
+        .collect();
+    // Names start with a millisecond timestamp
+    journals.sort();
+    Ok(journals)
+}
+
+/// Cut a journal back to its last checkpoint.
+///
+/// Samples written after the last checkpoint aren't covered by the header,
+/// and may end mid-sample. Returns the number of bytes removed.
+pub(crate) fn truncate_to_checkpoint(path: &Path) -> Result<u64> {
+    let file = File::options()
+        .read(true)
+        .write(true)
+        .open(path)
+        .context("Failed to open journal")?;
+    let len = file.metadata()?.len();
+
+    let reader = WavReader::new(file).context("Journal has no valid header")?;
+    let spec = reader.spec();
+    let data_len = reader.len() as u64 * (spec.bits_per_sample as u64 / 8);
+    // The reader stops right at the start of the sample data
+    let mut file = reader.into_inner();
+    let end = file.stream_position()? + data_len;
+
+    if end >= len {
+        return Ok(0);
+    }
+    file.set_len(end)?;
+    Ok(len - end)
+}
+
+/// Writes the journal for one recording.
+pub(crate) struct JournalWriter {
+    writer: WavWriter<BufWriter<File>>,
+    /// Second handle on the journal file, for syncing to disk
+    file: File,
+    path: PathBuf,
+    channels: u16,
+    samples: u64,
+    last_checkpoint: Instant,
+}
+
+impl JournalWriter {
+    /// Create a new journal in `dir`.
+    pub(crate) fn create(dir: &Path, spec: WavSpec) -> Result<Self> {
+        fs::create_dir_all(dir).context("Failed to create journal directory")?;
+        let path = dir.join(journal_file_name());
+        let file = File::options()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .context("Failed to create journal")?;
+        let sync_handle = file.try_clone()?;
+        let mut writer = WavWriter::new(BufWriter::new(file), spec)?;
+        // Get a valid, empty file on disk right away
+        writer.flush()?;
+
+        Ok(Self {
+            writer,
+            file: sync_handle,
+            path,
+            channels: spec.channels.max(1),
+            samples: 0,
+            last_checkpoint: Instant::now(),
+        })
+    }
+
+    /// Append one sample.
+    pub(crate) fn write_sample(&mut self, sample: f32) -> Result<()> {
+        self.writer.write_sample(sample)?;
+        self.samples += 1;
+        Ok(())
+    }
+
+    /// Checkpoint the journal if the interval has passed.
+    ///
+    /// Only whole frames make a valid file, so a checkpoint is put off while
+    /// a frame is partially written.
+    pub(crate) fn checkpoint_if_due(&mut self) -> Result<()> {
+        if self.last_checkpoint.elapsed() < CHECKPOINT_INTERVAL
+            || !self.samples.is_multiple_of(self.channels as u64)
+        {
+            return Ok(());
+        }
+
+        self.writer.flush()?;
+        self.file.sync_data()?;
+        self.last_checkpoint = Instant::now();
+        Ok(())
+    }
+
+    /// Finalize the journal and return its path.
+    pub(crate) fn finish(self) -> Result<PathBuf> {
+        self.writer.finalize()?;
+        if let Err(e) = self.file.sync_data() {
+            warn!(path = ?self.path, "Failed to sync journal: {}", e);
+        }
+        Ok(self.path)
+    }
+}
+
+fn journal_file_name() -> String {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default();
+    format!("{}{}-{}.wav", JOURNAL_PREFIX, millis, std::process::id())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    fn spec() -> WavSpec {
+        WavSpec {
+            channels: 2,
+            sample_rate: 16000,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        }
+    }
+
+    fn frames(path: &Path) -> u32 {
+        WavReader::open(path).unwrap().duration()
+    }
+
+    #[test]
+    fn test_checkpoint_survives_abandoned_writer() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut journal = JournalWriter::create(dir.path(), spec()).unwrap();
+        for i in 0..200 {
+            journal.write_sample(i as f32 / 200.0).unwrap();
+        }
+        journal.last_checkpoint -= CHECKPOINT_INTERVAL;
+        journal.checkpoint_if_due().unwrap();
+        // The process dies after a partial write past the checkpoint
+        let path = journal.path.clone();
+        std::mem::forget(journal);
+        File::options()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(&[0; 6])
+            .unwrap();
+
+        assert_eq!(frames(&path), 100);
+        assert_eq!(truncate_to_checkpoint(&path).unwrap(), 6);
+        assert_eq!(truncate_to_checkpoint(&path).unwrap(), 0);
+        assert_eq!(frames(&path), 100);
+    }
+
+    #[test]
+    fn test_checkpoint_waits_for_whole_frame() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut journal = JournalWriter::create(dir.path(), spec()).unwrap();
+        journal.write_sample(0.1).unwrap();
+        journal.last_checkpoint -= CHECKPOINT_INTERVAL;
+
+        journal.checkpoint_if_due().unwrap();
+
+        assert_eq!(frames(&journal.path), 0);
+    }
+
+    #[test]
+    fn test_orphaned_journals() {
+        let dir = tempfile::tempdir().unwrap();
+        let orphan = dir.path().join("journal-1-1.wav");
+        File::create(&orphan).unwrap().write_all(b"RIFF").unwrap();
+        File::create(dir.path().join("notes.txt")).unwrap();
+        let live = JournalWriter::create(dir.path(), spec()).unwrap();
+
+        let journals = orphaned_journals(dir.path()).unwrap();
+
+        assert_eq!(journals, vec![orphan]);
+        live.finish().unwrap();
+        assert!(
+            orphaned_journals(&dir.path().join("missing"))
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
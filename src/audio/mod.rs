@@ -0,0 +1,722 @@
+//! Audio recording module for whisp.
+//!
+//! This crate provides audio recording functionality using the system's
+//! default input device. It's platform-agnostic and uses channels for
+//! event communication instead of depending on any specific UI framework.
+//!
+//! ## Format notes
+//!
+//! WAV format uses ~467KiB every 5 seconds, hitting the 25MiB API limit
+//! in about 4m30s. This is sufficient for most dictation use cases.
+
+mod analysis;
+mod capture;
+mod gain;
+mod journal;
+mod resample;
+mod storage;
+
+use std::fs::{self, File};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+
+pub use analysis::{Analyzer, AudioAnalysis, analyze};
+use bytes::Bytes;
+use cpal::Host;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+pub use gain::{
+    AppliedGain, AutoGainConfig, NormalizeConfig, integrated_loudness, normalize, peak_dbfs,
+};
+use hound::{WavReader, WavSpec, WavWriter};
+pub use journal::{journal_dir, orphaned_journals};
+pub use resample::resample;
+pub use storage::{SpillConfig, recordings_dir};
+use thiserror::Error;
+use tracing::{error, info, warn};
+
+use self::capture::{CaptureProducer, CaptureWriter};
+use self::gain::AutoGain;
+use crate::core::{AudioEvent, MicState, RecordingState};
+
+/// Samples decoded at a time when streaming through a recording.
+const DECODE_BLOCK_SAMPLES: usize = 64 * 1024;
+
+/// Errors that can occur during recording.
+#[derive(Debug, Error)]
+pub enum RecorderError {
+    #[error(transparent)]
+    Anyhow(#[from] anyhow::Error),
+
+    #[error("no input device available")]
+    NoInputDevice,
+
+    #[error("sample format not supported: {0}")]
+    SampleFormatNotSupported(String),
+
+    #[error(transparent)]
+    BuildStream(#[from] cpal::BuildStreamError),
+}
+
+pub type Result<T> = std::result::Result<T, RecorderError>;
+
+/// Audio recorder using the system's default input device.
+pub struct Recorder {
+    host: Host,
+    auto_gain: Option<AutoGainConfig>,
+    spill: Option<SpillConfig>,
+    journal_dir: Option<PathBuf>,
+}
+
+impl Default for Recorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Recorder {
+    /// Create a new recorder.
+    pub fn new() -> Self {
+        Self {
+            host: cpal::default_host(),
+            auto_gain: None,
+            spill: None,
+            journal_dir: None,
+        }
+    }
+
+    /// Journal recordings to `dir` while they are in progress.
+    ///
+    /// The journal survives a crash and is deleted when the finished
+    /// recording is discarded, see [`orphaned_journals`].
+    pub fn with_journal(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.journal_dir = Some(dir.into());
+        self
+    }
+
+    /// Move recordings to a temp file once they grow past a threshold.
+    ///
+    /// Without this, recordings are kept entirely in memory.
+    pub fn with_spill(mut self, config: SpillConfig) -> Self {
+        self.spill = Some(config);
+        self
+    }
+
+    /// Apply automatic gain control to the samples as they are captured.
+    pub fn with_auto_gain(mut self, config: AutoGainConfig) -> Self {
+        self.auto_gain = Some(config);
+        self
+    }
+
+    /// Start recording audio.
+    ///
+    /// The `event_sender` is used to notify when the mic becomes active
+    /// (receives non-silent audio). Pass `None` if you don't need events.
+    pub fn start_recording(
+        &self,
+        event_sender: Option<Sender<AudioEvent>>,
+    ) -> Result<RecordingHandle> {
+        let device = self
+            .host
+            .default_input_device()
+            .ok_or(RecorderError::NoInputDevice)?;
+        let config = device
+            .default_input_config()
+            .map_err(|_| RecorderError::NoInputDevice)?;
+
+        info!(
+            device_name = %device.name().unwrap_or_default(),
+            config = ?config,
+            "Recording from device"
+        );
+
+        let spec = wav_spec_from_config(&config);
+
+        let auto_gain = self
+            .auto_gain
+            .map(|agc| AutoGain::new(agc, spec.sample_rate, spec.channels));
+        let (mut producer, capture) = capture::start(
+            spec,
+            auto_gain,
+            self.spill.clone(),
+            self.journal_dir.as_deref(),
+        )?;
+
+        let err_fn = move |err| {
+            error!("an error occurred on stream: {}", err);
+        };
+
+        let mut state = RecordingState::default();
+
+        let stream = match config.sample_format() {
+            cpal::SampleFormat::F32 => device.build_input_stream(
+                &config.into(),
+                move |data, _: &_| write_data(&mut state, data, &mut producer, &event_sender),
+                err_fn,
+                None,
+            )?,
+            sample_format => {
+                return Err(RecorderError::SampleFormatNotSupported(format!(
+                    "{:?}",
+                    sample_format
+                )));
+            }
+        };
+
+        stream
+            .play()
+            .map_err(|_| anyhow::anyhow!("failed to play stream"))?;
+
+        Ok(RecordingHandle {
+            stream,
+            capture: Some(capture),
+        })
+    }
+}
+
+/// Handle to an active recording.
+///
+/// Call `finish()` to stop recording and retrieve the audio data.
+/// If dropped without calling `finish()`, the recording will be finalized
+/// but you won't be able to retrieve the data.
+pub struct RecordingHandle {
+    stream: cpal::Stream,
+    capture: Option<CaptureWriter>,
+}
+
+/// A completed recording with audio data.
+///
+/// Long recordings may be backed by a memory-mapped temp file, see
+/// [`Recorder::with_spill`]. The file is kept until [`Recording::discard`]
+/// is called, so a failed transcription can still be recovered from disk.
+/// The same goes for the recording's journal, see [`Recorder::with_journal`].
+pub struct Recording {
+    data: Bytes,
+    spill_path: Option<PathBuf>,
+    journal_path: Option<PathBuf>,
+    spec: WavSpec,
+    frames: u64,
+    dropped_samples: u64,
+    overruns: u64,
+}
+
+/// Outcome of [`Recording::normalize`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NormalizeOutcome {
+    /// The level was normalized
+    Applied(AppliedGain),
+    /// The level couldn't be measured (silence, or too short for a loudness
+    /// measurement)
+    TooQuiet,
+    /// The recording spilled to disk and is too large to re-encode in memory
+    Spilled,
+}
+
+impl Recording {
+    /// Create a recording from WAV data, reading the format from its header.
+    pub fn from_wav(data: impl Into<Bytes>) -> Result<Self> {
+        let data = data.into();
+        let (spec, frames) = WavReader::new(Cursor::new(&data))
+            .map(|reader| (reader.spec(), reader.duration() as u64))
+            .map_err(|e| RecorderError::Anyhow(e.into()))?;
+
+        Ok(Self {
+            data,
+            spill_path: None,
+            journal_path: None,
+            spec,
+            frames,
+            dropped_samples: 0,
+            overruns: 0,
+        })
+    }
+
+    /// Recover a recording from a journal left behind by a crash.
+    ///
+    /// Audio after the journal's last checkpoint is cut off. The journal is
+    /// deleted when the recording is discarded.
+    pub fn from_journal(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let removed = journal::truncate_to_checkpoint(&path)?;
+        if removed > 0 {
+            info!(path = ?path, bytes = removed, "Dropped journal data after last checkpoint");
+        }
+
+        let data = File::open(&path)
+            .and_then(|file| storage::map_file(&file))
+            .map_err(|e| RecorderError::Anyhow(e.into()))?;
+        Ok(Self {
+            journal_path: Some(path),
+            ..Self::from_wav(data)?
+        })
+    }
+
+    /// Get the raw audio data (WAV format).
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Get the WAV specification.
+    pub fn spec(&self) -> &WavSpec {
+        &self.spec
+    }
+
+    /// Get the number of channels.
+    pub fn channels(&self) -> u16 {
+        self.spec.channels
+    }
+
+    /// Get the sample rate in Hz.
+    pub fn sample_rate(&self) -> u32 {
+        self.spec.sample_rate
+    }
+
+    /// Get the number of frames (samples per channel) in the recording.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Get the number of samples in the recording, across all channels.
+    pub fn samples(&self) -> u64 {
+        self.frames * self.spec.channels as u64
+    }
+
+    /// Get the duration of the recording.
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.frames as f64 / self.spec.sample_rate as f64)
+    }
+
+    /// Decode the recording into interleaved f32 samples.
+    pub fn decode(&self) -> Result<Vec<f32>> {
+        decode_samples(&self.data)
+    }
+
+    /// Analyze levels, clipping and speech content.
+    ///
+    /// The recording is decoded block by block, so this works for spilled
+    /// recordings too, but it reads all of it: call it once and keep the
+    /// result.
+    pub fn analyze(&self) -> Result<AudioAnalysis> {
+        let mut analyzer = Analyzer::new(self.spec.channels, self.spec.sample_rate);
+        decode_blocks(&self.data, |block| analyzer.push(block))?;
+        Ok(analyzer.finish())
+    }
+
+    /// Get the number of samples dropped because the writer fell behind.
+    ///
+    /// Non-zero means the audio has gaps.
+    pub fn dropped_samples(&self) -> u64 {
+        self.dropped_samples
+    }
+
+    /// Get the number of audio callbacks that had to drop samples.
+    pub fn overruns(&self) -> u64 {
+        self.overruns
+    }
+
+    /// Get a cheap, reference-counted handle to the audio data (WAV format).
+    pub fn bytes(&self) -> Bytes {
+        self.data.clone()
+    }
+
+    /// Get the temp file backing this recording, if it spilled to disk.
+    pub fn spill_path(&self) -> Option<&Path> {
+        self.spill_path.as_deref()
+    }
+
+    /// Get the journal of this recording, if it has one.
+    pub fn journal_path(&self) -> Option<&Path> {
+        self.journal_path.as_deref()
+    }
+
+    /// Consume the recording and return the raw data.
+    ///
+    /// Spill and journal files, if any, are left on disk.
+    pub fn into_data(self) -> Bytes {
+        self.data
+    }
+
+    /// Drop the recording and delete its spill and journal files, if any.
+    pub fn discard(self) {
+        let Self {
+            data,
+            spill_path,
+            journal_path,
+            ..
+        } = self;
+        // Unmap before deleting the file backing the data
+        drop(data);
+
+        for path in spill_path.iter().chain(&journal_path) {
+            if let Err(e) = fs::remove_file(path) {
+                warn!(path = ?path, "Failed to remove recording file: {}", e);
+            }
+        }
+    }
+
+    /// Normalize the recording's level in place.
+    ///
+    /// Recordings that spilled to disk are too large to re-encode in memory
+    /// and are left as they are.
+    pub fn normalize(&mut self, config: &NormalizeConfig) -> Result<NormalizeOutcome> {
+        if self.spill_path.is_some() {
+            return Ok(NormalizeOutcome::Spilled);
+        }
+
+        let mut samples = decode_samples(&self.data)?;
+        let Some(applied) = normalize(
+            &mut samples,
+            self.spec.channels,
+            self.spec.sample_rate,
+            config,
+        ) else {
+            return Ok(NormalizeOutcome::TooQuiet);
+        };
+        self.data = encode_samples(self.spec, &samples)?.into();
+        Ok(NormalizeOutcome::Applied(applied))
+    }
+}
+
+impl RecordingHandle {
+    /// Pause the recording without finalizing it.
+    ///
+    /// Samples are skipped until [`resume`](Self::resume) is called, so the
+    /// finished recording has no gap and is transcribed in one piece.
+    pub fn pause(&self) {
+        if let Some(capture) = &self.capture {
+            info!("pausing recording");
+            capture.set_paused(true);
+        }
+    }
+
+    /// Resume a paused recording.
+    pub fn resume(&self) {
+        if let Some(capture) = &self.capture {
+            info!("resuming recording");
+            capture.set_paused(false);
+        }
+    }
+
+    /// Whether the recording is paused.
+    pub fn is_paused(&self) -> bool {
+        self.capture.as_ref().is_some_and(|c| c.is_paused())
+    }
+
+    /// Finish the recording and return the audio data.
+    pub fn finish(&mut self) -> Result<Option<Recording>> {
+        let Some(capture) = self.capture.take() else {
+            return Ok(None);
+        };
+
+        info!("ending recording");
+        self.stream.pause().ok();
+
+        let output = capture.finish()?;
+
+        if let Some(gain_db) = output.auto_gain_db {
+            info!(gain_db, "automatic gain control applied");
+        }
+        if output.dropped_samples > 0 {
+            warn!(
+                dropped_samples = output.dropped_samples,
+                overruns = output.overruns,
+                "Audio samples were dropped during recording"
+            );
+        }
+
+        Ok(Some(Recording {
+            spill_path: output.audio.path,
+            journal_path: output.journal_path,
+            dropped_samples: output.dropped_samples,
+            overruns: output.overruns,
+            ..Recording::from_wav(output.audio.data)?
+        }))
+    }
+}
+
+impl Drop for RecordingHandle {
+    fn drop(&mut self) {
+        if self.capture.is_some()
+            && let Err(e) = self.finish()
+        {
+            error!("failed to finalize recording: {}", e);
+        }
+    }
+}
+
+fn wav_spec_from_config(config: &cpal::SupportedStreamConfig) -> hound::WavSpec {
+    hound::WavSpec {
+        channels: config.channels(),
+        sample_rate: config.sample_rate().0,
+        bits_per_sample: (config.sample_format().sample_size() * 8) as _,
+        sample_format: sample_format(config.sample_format()),
+    }
+}
+
+fn sample_format(format: cpal::SampleFormat) -> hound::SampleFormat {
+    if format.is_float() {
+        hound::SampleFormat::Float
+    } else {
+        hound::SampleFormat::Int
+    }
+}
+
+/// Decode WAV data into interleaved f32 samples.
+fn decode_samples(data: &[u8]) -> Result<Vec<f32>> {
+    let reader = WavReader::new(Cursor::new(data)).map_err(|e| RecorderError::Anyhow(e.into()))?;
+    let spec = reader.spec();
+    let samples: std::result::Result<Vec<f32>, hound::Error> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.into_samples::<f32>().collect(),
+        hound::SampleFormat::Int => {
+            let max_val = (1u32 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .into_samples::<i32>()
+                .map(|s| s.map(|s| s as f32 / max_val))
+                .collect()
+        }
+    };
+    samples.map_err(|e| RecorderError::Anyhow(e.into()))
+}
+
+/// Decode WAV data to interleaved f32 samples [`DECODE_BLOCK_SAMPLES`] at
+/// a time, without holding all of them in memory.
+fn decode_blocks(data: &[u8], mut f: impl FnMut(&[f32])) -> Result<()> {
+    let mut reader =
+        WavReader::new(Cursor::new(data)).map_err(|e| RecorderError::Anyhow(e.into()))?;
+    let spec = reader.spec();
+    let mut samples: Box<dyn Iterator<Item = hound::Result<f32>>> = match spec.sample_format {
+        hound::SampleFormat::Float => Box::new(reader.samples::<f32>()),
+        hound::SampleFormat::Int => {
+            let max_val = (1u32 << (spec.bits_per_sample - 1)) as f32;
+            Box::new(
+                reader
+                    .samples::<i32>()
+                    .map(move |s| s.map(|s| s as f32 / max_val)),
+            )
+        }
+    };
+
+    let mut block = Vec::with_capacity(DECODE_BLOCK_SAMPLES);
+    loop {
+        block.clear();
+        for sample in samples.by_ref().take(DECODE_BLOCK_SAMPLES) {
+            block.push(sample.map_err(|e| RecorderError::Anyhow(e.into()))?);
+        }
+        if block.is_empty() {
+            return Ok(());
+        }
+        f(&block);
+    }
+}
+
+/// Encode interleaved f32 samples as WAV data with the given spec.
+fn encode_samples(spec: WavSpec, samples: &[f32]) -> Result<Vec<u8>> {
+    let mut cursor = Cursor::new(Vec::with_capacity(
+        samples.len() * (spec.bits_per_sample / 8) as usize + 44,
+    ));
+    let mut writer =
+        WavWriter::new(&mut cursor, spec).map_err(|e| RecorderError::Anyhow(e.into()))?;
+
+    let written = match spec.sample_format {
+        hound::SampleFormat::Float => samples.iter().try_for_each(|&s| writer.write_sample(s)),
+        hound::SampleFormat::Int => {
+            let max_val = (1u32 << (spec.bits_per_sample - 1)) as f32;
+            samples.iter().try_for_each(|&s| {
+                writer.write_sample((s * max_val).clamp(-max_val, max_val - 1.0) as i32)
+            })
+        }
+    };
+    written
+        .and_then(|_| writer.finalize())
+        .map_err(|e| RecorderError::Anyhow(e.into()))?;
+
+    Ok(cursor.into_inner())
+}
+
+fn write_data(
+    state: &mut RecordingState,
+    data: &[f32],
+    producer: &mut CaptureProducer,
+    event_sender: &Option<Sender<AudioEvent>>,
+) {
+    if producer.is_paused() {
+        return;
+    }
+
+    if !state.mic_active {
+        if data.iter().any(|&sample| sample != 0.0) {
+            state.mic_active = true;
+            if let Some(sender) = event_sender {
+                sender.send(AudioEvent::StateChanged(MicState::Active)).ok();
+            }
+        } else {
+            return;
+        }
+    }
+
+    producer.push(data);
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn wav(spec: WavSpec, samples: &[f32]) -> Recording {
+        Recording::from_wav(encode_samples(spec, samples).unwrap()).unwrap()
+    }
+
+    pub(crate) fn float_spec(channels: u16, sample_rate: u32) -> WavSpec {
+        WavSpec {
+            channels,
+            sample_rate,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        }
+    }
+
+    /// Broadband clicks like a keyboard's, every 150 ms over a faint hiss.
+    pub(crate) fn clicks(sample_rate: u32, secs: f32) -> Vec<f32> {
+        let mut seed = 1u32;
+        let period = (sample_rate as f32 * 0.15) as usize;
+        let click = (sample_rate as f32 * 0.005) as usize;
+        (0..(sample_rate as f32 * secs) as usize)
+            .map(|i| {
+                seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+                let noise = (seed >> 8) as f32 / (1 << 23) as f32 - 1.0;
+                let t = i % period;
+                if t < click {
+                    0.5 * (1.0 - t as f32 / click as f32) * noise
+                } else {
+                    0.001 * noise
+                }
+            })
+            .collect()
+    }
+
+    fn tone(amplitude: f32, sample_rate: u32, secs: f32) -> Vec<f32> {
+        let len = (sample_rate as f32 * secs) as usize;
+        (0..len)
+            .map(|i| {
+                let t = i as f32 / sample_rate as f32;
+                amplitude * (2.0 * std::f32::consts::PI * 440.0 * t).sin()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_stereo_duration_counts_frames() {
+        let recording = wav(float_spec(2, 48000), &vec![0.0; 48000 * 2 / 4]);
+
+        assert_eq!(recording.channels(), 2);
+        assert_eq!(recording.frames(), 12000);
+        assert_eq!(recording.samples(), 24000);
+        assert_eq!(recording.duration(), Duration::from_millis(250));
+    }
+
+    #[test]
+    fn test_int_metadata() {
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: 16000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let recording = wav(spec, &tone(0.5, 16000, 1.5));
+
+        assert_eq!(recording.sample_rate(), 16000);
+        assert_eq!(recording.frames(), 24000);
+        assert_eq!(recording.duration(), Duration::from_millis(1500));
+        assert!((recording.analyze().unwrap().peak - 0.5).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_analysis_finds_speech_and_silence() {
+        let mut samples = vec![0.0; 8000];
+        samples.extend(tone(0.5, 16000, 1.0));
+        samples.extend(vec![0.0; 4000]);
+        let analysis = wav(float_spec(1, 16000), &samples).analyze().unwrap();
+
+        assert!((analysis.peak - 0.5).abs() < 0.001);
+        assert!((analysis.rms - 0.5 / 2f32.sqrt() / 1.75f32.sqrt()).abs() < 0.01);
+        assert!((analysis.speech_ratio - 1.0 / 1.75).abs() < 0.02);
+        assert_eq!(analysis.leading_silence, Duration::from_millis(500));
+        assert_eq!(analysis.trailing_silence, Duration::from_millis(250));
+        assert_eq!(analysis.clipping_percent, 0.0);
+    }
+
+    #[test]
+    fn test_analysis_of_silence() {
+        let analysis = wav(float_spec(2, 16000), &vec![0.0; 32000])
+            .analyze()
+            .unwrap();
+
+        assert_eq!(analysis.speech_ratio, 0.0);
+        assert_eq!(analysis.speech_duration(), Duration::ZERO);
+        assert_eq!(analysis.leading_silence, Duration::from_secs(1));
+        assert_eq!(analysis.trailing_silence, Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_analysis_in_blocks() {
+        let mut samples = vec![0.0; 6000];
+        samples.extend(tone(0.4, 16000, 0.5));
+        samples.extend(vec![0.0; 3000]);
+        let expected = analyze(&samples, 2, 16000);
+
+        // Blocks that split both sample frames and analysis frames
+        let mut analyzer = Analyzer::new(2, 16000);
+        for block in samples.chunks(333) {
+            analyzer.push(block);
+        }
+
+        assert_eq!(analyzer.finish(), expected);
+        assert_eq!(
+            wav(float_spec(2, 16000), &samples).analyze().unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_analysis_ignores_clicks() {
+        let analysis = wav(float_spec(1, 16000), &clicks(16000, 5.0))
+            .analyze()
+            .unwrap();
+
+        assert_eq!(analysis.speech_duration(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_clipping_percent() {
+        let mut samples = tone(0.3, 16000, 0.9);
+        samples.extend(vec![1.0; 1600]);
+        let recording = wav(float_spec(1, 16000), &samples);
+
+        assert!((recording.analyze().unwrap().clipping_percent - 10.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_paused_capture_skips_samples() {
+        let (mut producer, capture) =
+            capture::start(float_spec(1, 16000), None, None, None).unwrap();
+        let (sender, events) = std::sync::mpsc::channel();
+        let sender = Some(sender);
+        let mut state = RecordingState::default();
+
+        capture.set_paused(true);
+        write_data(&mut state, &[0.5; 100], &mut producer, &sender);
+        assert!(!state.mic_active);
+        assert!(events.try_recv().is_err());
+
+        capture.set_paused(false);
+        write_data(&mut state, &[0.25; 100], &mut producer, &sender);
+        assert!(matches!(
+            events.try_recv(),
+            Ok(AudioEvent::StateChanged(MicState::Active))
+        ));
+
+        let output = capture.finish().unwrap();
+        let recording = Recording::from_wav(output.audio.data).unwrap();
+        assert_eq!(recording.decode().unwrap(), vec![0.25; 100]);
+    }
+}
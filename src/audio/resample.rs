@@ -0,0 +1,175 @@
+//! Band-limited sample rate conversion.
+//!
+//! Resamples with a Kaiser-windowed sinc filter. When downsampling, the
+//! filter's cutoff sits just below the new Nyquist frequency, so content
+//! that can't be represented at the new rate is removed instead of folding
+//! back into the speech band as aliasing.
+
+use std::f64::consts::PI;
+use std::sync::OnceLock;
+
+/// Zero crossings of the sinc on each side of the center.
+const ZERO_CROSSINGS: usize = 16;
+
+/// Kernel table entries per zero crossing.
+const TABLE_RESOLUTION: usize = 512;
+
+/// Cutoff as a fraction of the lower Nyquist frequency, leaving room for
+/// the filter's transition band.
+const ROLLOFF: f64 = 0.92;
+
+/// Kaiser window shape, about 80 dB of stopband attenuation.
+const KAISER_BETA: f64 = 8.0;
+
+/// Resample mono `samples` from `from_rate` to `to_rate`.
+///
+/// The output has `samples.len() * to_rate / from_rate` samples, rounded
+/// down. Audio past either end of the input is treated as silence.
+pub fn resample(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    if from_rate == to_rate || from_rate == 0 || to_rate == 0 {
+        return samples.to_vec();
+    }
+
+    let table = kernel_table();
+    let step = from_rate as f64 / to_rate as f64;
+    // Kernel scale, in zero crossings per input sample
+    let scale = (to_rate as f64 / from_rate as f64).min(1.0) * ROLLOFF;
+    let reach = ZERO_CROSSINGS as f64 / scale;
+    let new_len = (samples.len() as u64 * to_rate as u64 / from_rate as u64) as usize;
+
+    (0..new_len)
+        .map(|i| {
+            let center = i as f64 * step;
+            let first = (center - reach).ceil().max(0.0) as usize;
+            let last = ((center + reach).floor() as usize).min(samples.len() - 1);
+
+            let sum: f64 = (first..=last)
+                .map(|k| samples[k] as f64 * kernel(table, (center - k as f64) * scale))
+                .sum();
+            (sum * scale) as f32
+        })
+        .collect()
+}
+
+/// Windowed sinc at `x` zero crossings from the center, interpolated from
+/// the table.
+fn kernel(table: &[f64], x: f64) -> f64 {
+    let position = x.abs() * TABLE_RESOLUTION as f64;
+    let index = position as usize;
+    if index + 1 >= table.len() {
+        return 0.0;
+    }
+    let frac = position - index as f64;
+    table[index] + (table[index + 1] - table[index]) * frac
+}
+
+/// One side of the windowed sinc, from the center to the last zero crossing.
+fn kernel_table() -> &'static [f64] {
+    static TABLE: OnceLock<Vec<f64>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let len = ZERO_CROSSINGS * TABLE_RESOLUTION + 1;
+        let norm = bessel_i0(KAISER_BETA);
+        (0..len)
+            .map(|i| {
+                let x = i as f64 / TABLE_RESOLUTION as f64;
+                let sinc = if i == 0 {
+                    1.0
+                } else {
+                    (PI * x).sin() / (PI * x)
+                };
+                let r = x / ZERO_CROSSINGS as f64;
+                let window = bessel_i0(KAISER_BETA * (1.0 - r * r).max(0.0).sqrt()) / norm;
+                sinc * window
+            })
+            .collect()
+    })
+}
+
+/// Zeroth-order modified Bessel function of the first kind.
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let half = x / 2.0;
+    for k in 1..50 {
+        term *= (half / k as f64) * (half / k as f64);
+        sum += term;
+        if term < sum * 1e-12 {
+            break;
+        }
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(freq: f32, sample_rate: u32, secs: f32) -> Vec<f32> {
+        let n = (sample_rate as f32 * secs) as usize;
+        (0..n)
+            .map(|i| (2.0 * std::f32::consts::PI * freq * i as f32 / sample_rate as f32).sin())
+            .collect()
+    }
+
+    /// RMS level in dBFS, ignoring the filter's ramp at both ends.
+    fn rms_db(samples: &[f32]) -> f32 {
+        let inner = &samples[samples.len() / 10..samples.len() * 9 / 10];
+        let mean_square = inner.iter().map(|s| s * s).sum::<f32>() / inner.len() as f32;
+        10.0 * mean_square.log10()
+    }
+
+    /// Magnitude of the `freq` component, via the Goertzel algorithm.
+    fn magnitude(samples: &[f32], freq: f32, sample_rate: u32) -> f32 {
+        let coeff = 2.0 * (2.0 * std::f32::consts::PI * freq / sample_rate as f32).cos();
+        let (mut s1, mut s2) = (0.0f32, 0.0f32);
+        for &x in samples {
+            let s = x + coeff * s1 - s2;
+            s2 = s1;
+            s1 = s;
+        }
+        (s1 * s1 + s2 * s2 - coeff * s1 * s2).sqrt() / samples.len() as f32 * 2.0
+    }
+
+    #[test]
+    fn test_output_length() {
+        assert_eq!(resample(&vec![0.0; 48000], 48000, 16000).len(), 16000);
+        assert_eq!(resample(&vec![0.0; 44100], 44100, 16000).len(), 16000);
+        assert_eq!(resample(&vec![0.0; 8000], 8000, 16000).len(), 16000);
+        assert_eq!(resample(&[0.5, 0.25], 16000, 16000), vec![0.5, 0.25]);
+    }
+
+    #[test]
+    fn test_resample() {
+        // Simple test: downsampling should produce fewer samples
+        let samples: Vec<f32> = (0..48000).map(|i| (i as f32 / 48000.0).sin()).collect();
+        let resampled = resample(&samples, 48000, 16000);
+        assert_eq!(resampled.len(), 16000);
+    }
+
+    #[test]
+    fn test_rejects_tone_above_new_nyquist() {
+        // Would alias to 6 kHz with linear interpolation
+        let resampled = resample(&sine(10_000.0, 48000, 1.0), 48000, 16000);
+        assert!(rms_db(&resampled) < -60.0, "{} dB", rms_db(&resampled));
+    }
+
+    #[test]
+    fn test_passes_speech_band() {
+        for (freq, from_rate) in [
+            (440.0, 48000),
+            (1000.0, 44100),
+            (3000.0, 48000),
+            (1000.0, 8000),
+        ] {
+            let resampled = resample(&sine(freq, from_rate, 1.0), from_rate, 16000);
+            // A full-scale sine is -3 dBFS
+            let level = rms_db(&resampled);
+            assert!(
+                (level + 3.01).abs() < 0.1,
+                "{freq} Hz from {from_rate}: {level} dB"
+            );
+            let inner = &resampled[1600..14400];
+            assert!((magnitude(inner, freq, 16000) - 1.0).abs() < 0.02);
+        }
+    }
+}
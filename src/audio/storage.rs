@@ -0,0 +1,210 @@
+//! Recording storage that spills to disk.
+//!
+//! Recordings start out in memory. An hour-long meeting in 48 kHz stereo f32
+//! is about 1.3 GB though, so once a recording grows past the spill
+//! threshold it moves to a temp file and continues streaming there. The
+//! finished file is memory-mapped so transcribers still get plain `Bytes`.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Cursor, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use bytes::Bytes;
+use memmap2::Mmap;
+use tracing::info;
+
+use crate::core::{Config, default_data_dir};
+
+/// Settings for moving large recordings out of memory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpillConfig {
+    /// Size in bytes past which a recording moves to disk
+    pub threshold: u64,
+    /// Directory for spilled recordings
+    pub dir: PathBuf,
+}
+
+impl SpillConfig {
+    /// Create spill settings with a threshold and directory.
+    pub fn new(threshold: u64, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            threshold,
+            dir: dir.into(),
+        }
+    }
+
+    /// Build spill settings from the app config.
+    ///
+    /// Spilled recordings go to `recordings/` under the data directory.
+    pub fn from_config(config: &Config) -> Result<Self> {
+        Ok(Self::new(
+            config.spill_threshold_mb as u64 * 1024 * 1024,
+            recordings_dir()?,
+        ))
+    }
+}
+
+/// Returns the directory where spilled recordings are stored.
+pub fn recordings_dir() -> Result<PathBuf> {
+    Ok(default_data_dir()?.join("recordings"))
+}
+
+/// Finished recording data and the file backing it, if it spilled.
+pub(crate) struct StoredAudio {
+    pub data: Bytes,
+    pub path: Option<PathBuf>,
+}
+
+enum Sink {
+    Memory(Cursor<Vec<u8>>),
+    File {
+        file: BufWriter<File>,
+        path: PathBuf,
+    },
+}
+
+/// A `Write + Seek` sink that starts in memory and moves to a temp file
+/// once it grows past the spill threshold.
+pub(crate) struct SpillWriter {
+    sink: Sink,
+    spill: Option<SpillConfig>,
+}
+
+impl SpillWriter {
+    /// Create a writer. With no spill config it stays in memory.
+    pub(crate) fn new(spill: Option<SpillConfig>) -> Self {
+        Self {
+            sink: Sink::Memory(Cursor::new(Vec::with_capacity(8 * 1024))),
+            spill,
+        }
+    }
+
+    /// Flush and hand out the written data.
+    ///
+    /// Spilled data is memory-mapped rather than read back into memory.
+    pub(crate) fn finish(self) -> io::Result<StoredAudio> {
+        match self.sink {
+            Sink::Memory(cursor) => Ok(StoredAudio {
+                data: Bytes::from(cursor.into_inner()),
+                path: None,
+            }),
+            Sink::File { file, path } => {
+                let file = file.into_inner().map_err(|e| e.into_error())?;
+                file.sync_all()?;
+                Ok(StoredAudio {
+                    data: map_file(&file)?,
+                    path: Some(path),
+                })
+            }
+        }
+    }
+
+    /// Move the in-memory buffer to a temp file, keeping the position.
+    fn spill_to_file(&mut self, dir: &Path) -> io::Result<()> {
+        let Sink::Memory(cursor) = &self.sink else {
+            return Ok(());
+        };
+
+        fs::create_dir_all(dir)?;
+        let path = dir.join(spill_file_name());
+        let mut file = BufWriter::new(
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create_new(true)
+                .open(&path)?,
+        );
+        file.write_all(cursor.get_ref())?;
+        file.seek(SeekFrom::Start(cursor.position()))?;
+
+        info!(path = ?path, bytes = cursor.get_ref().len(), "Recording spilled to disk");
+        self.sink = Sink::File { file, path };
+        Ok(())
+    }
+}
+
+impl Write for SpillWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let (Sink::Memory(cursor), Some(spill)) = (&self.sink, &self.spill)
+            && cursor.get_ref().len() as u64 + buf.len() as u64 > spill.threshold
+        {
+            let dir = spill.dir.clone();
+            self.spill_to_file(&dir)?;
+        }
+
+        match &mut self.sink {
+            Sink::Memory(cursor) => cursor.write(buf),
+            Sink::File { file, .. } => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.sink {
+            Sink::Memory(cursor) => cursor.flush(),
+            Sink::File { file, .. } => file.flush(),
+        }
+    }
+}
+
+impl Seek for SpillWriter {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match &mut self.sink {
+            Sink::Memory(cursor) => cursor.seek(pos),
+            Sink::File { file, .. } => file.seek(pos),
+        }
+    }
+}
+
+/// Memory-map a finished recording file.
+pub(crate) fn map_file(file: &File) -> io::Result<Bytes> {
+    // SAFETY: recording files are private to whisp and nothing writes to
+    // them once they are finished.
+    let mmap = unsafe { Mmap::map(file)? };
+    Ok(Bytes::from_owner(mmap))
+}
+
+fn spill_file_name() -> String {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default();
+    format!("recording-{}-{}.wav", millis, std::process::id())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stays_in_memory_below_threshold() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut writer = SpillWriter::new(Some(SpillConfig::new(1024, dir.path())));
+        writer.write_all(&[1; 512]).unwrap();
+
+        let stored = writer.finish().unwrap();
+
+        assert!(stored.path.is_none());
+        assert_eq!(stored.data.len(), 512);
+    }
+
+    #[test]
+    fn test_spills_past_threshold() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut writer = SpillWriter::new(Some(SpillConfig::new(1024, dir.path())));
+        writer.write_all(&[1; 1000]).unwrap();
+        writer.write_all(&[2; 1000]).unwrap();
+        // Seeking back must patch the spilled file, like a WAV header update
+        writer.seek(SeekFrom::Start(0)).unwrap();
+        writer.write_all(&[3; 4]).unwrap();
+
+        let stored = writer.finish().unwrap();
+
+        let path = stored.path.expect("recording should spill");
+        assert!(path.starts_with(dir.path()));
+        assert_eq!(stored.data.len(), 2000);
+        assert_eq!(&stored.data[..5], &[3, 3, 3, 3, 1]);
+        assert_eq!(stored.data[1999], 2);
+    }
+}
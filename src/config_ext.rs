@@ -16,10 +16,32 @@ pub fn default_hotkey() -> HotKey {
     )
 }
 
+/// Default pause hotkey: Meta+Shift+Quote
+pub fn default_pause_hotkey() -> HotKey {
+    HotKey::new(
+        Some(Modifiers::META | Modifiers::SHIFT),
+        global_hotkey::hotkey::Code::Quote,
+    )
+}
+
+/// Default cancel hotkey: Meta+Shift+Escape
+pub fn default_cancel_hotkey() -> HotKey {
+    HotKey::new(
+        Some(Modifiers::META | Modifiers::SHIFT),
+        global_hotkey::hotkey::Code::Escape,
+    )
+}
+
 /// Extension trait for Config to handle hotkeys.
 pub trait ConfigExt {
     /// Get the hotkey, parsing from config or using default.
     fn hotkey(&self) -> HotKey;
+
+    /// Get the hotkey that pauses and resumes a recording.
+    fn pause_hotkey(&self) -> HotKey;
+
+    /// Get the hotkey that cancels the recording and transcriptions.
+    fn cancel_hotkey(&self) -> HotKey;
 }
 
 impl ConfigExt for Config {
@@ -28,10 +50,26 @@ impl ConfigExt for Config {
         // TODO: Parse from config.hotkey string if present
         default_hotkey()
     }
+
+    fn pause_hotkey(&self) -> HotKey {
+        default_pause_hotkey()
+    }
+
+    fn cancel_hotkey(&self) -> HotKey {
+        default_cancel_hotkey()
+    }
 }
 
 impl ConfigExt for Arc<RwLock<Config>> {
     fn hotkey(&self) -> HotKey {
         self.read().unwrap().hotkey()
     }
+
+    fn pause_hotkey(&self) -> HotKey {
+        self.read().unwrap().pause_hotkey()
+    }
+
+    fn cancel_hotkey(&self) -> HotKey {
+        self.read().unwrap().cancel_hotkey()
+    }
 }
@@ -3,6 +3,7 @@
 //! This module provides core configuration that doesn't depend on
 //! platform-specific UI libraries.
 
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::PathBuf;
 use std::time::Duration;
@@ -14,6 +15,9 @@ use tracing::warn;
 
 use crate::APP_NAME;
 
+/// Marker starting each speaker turn, a dialogue dash.
+const DEFAULT_SPEAKER_MARKER: &str = "— ";
+
 /// Transcription backend to use.
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
@@ -38,6 +42,52 @@ impl Default for TranscriptionBackend {
     }
 }
 
+/// How the API key is sent to an OpenAI-compatible server.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AuthStyle {
+    /// `Authorization: Bearer <key>`, used by OpenAI
+    #[default]
+    Bearer,
+    /// `api-key: <key>`, used by Azure OpenAI
+    ApiKey,
+}
+
+/// Loudness normalization applied to recordings before transcription.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Normalization {
+    /// Leave the recording untouched
+    #[default]
+    Off,
+    /// Scale so the loudest sample hits the target (dBFS)
+    Peak,
+    /// Scale so the integrated loudness hits the target (LUFS, ITU-R BS.1770)
+    Loudness,
+}
+
+impl Normalization {
+    /// Returns the target used when `normalize_target` is not set.
+    pub fn default_target(&self) -> f32 {
+        match self {
+            Normalization::Off => 0.0,
+            Normalization::Peak => -1.0,
+            Normalization::Loudness => -16.0,
+        }
+    }
+}
+
+/// What to produce from speech.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Task {
+    /// Text in the spoken language
+    #[default]
+    Transcribe,
+    /// English text, whatever the spoken language
+    Translate,
+}
+
 /// Returns the default data directory for whisp.
 ///
 /// This is where downloaded models and other data are stored.
@@ -51,8 +101,49 @@ pub fn models_dir() -> Result<PathBuf> {
     Ok(default_data_dir()?.join("models"))
 }
 
-fn is_default_backend(v: &TranscriptionBackend) -> bool {
-    *v == TranscriptionBackend::default()
+fn default_backends() -> Vec<TranscriptionBackend> {
+    vec![TranscriptionBackend::default()]
+}
+
+fn is_default_backends(v: &[TranscriptionBackend]) -> bool {
+    v == default_backends()
+}
+
+/// (De)serializes backends as a single name or an ordered list of names.
+mod one_or_many {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::TranscriptionBackend;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(TranscriptionBackend),
+        Many(Vec<TranscriptionBackend>),
+    }
+
+    pub fn serialize<S: Serializer>(
+        backends: &[TranscriptionBackend],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match backends {
+            [backend] => backend.serialize(serializer),
+            backends => backends.serialize(serializer),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<TranscriptionBackend>, D::Error> {
+        match OneOrMany::deserialize(deserializer)? {
+            OneOrMany::One(backend) => Ok(vec![backend]),
+            OneOrMany::Many(backends) if backends.is_empty() => {
+                Err(D::Error::custom("backend list is empty"))
+            }
+            OneOrMany::Many(backends) => Ok(backends),
+        }
+    }
 }
 
 /// Core configuration structure for the application.
@@ -61,28 +152,131 @@ fn is_default_backend(v: &TranscriptionBackend) -> bool {
 /// settings like hotkeys are handled separately by the main application.
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Config {
-    /// Transcription backend to use (openai or local)
-    #[serde(default, skip_serializing_if = "is_default_backend")]
-    pub backend: TranscriptionBackend,
+    /// Transcription backend to use (openai or local), or a list of backends
+    /// to try in order, e.g. `["openai", "local"]`
+    #[serde(
+        default = "default_backends",
+        skip_serializing_if = "is_default_backends",
+        with = "one_or_many"
+    )]
+    pub backend: Vec<TranscriptionBackend>,
 
-    /// OpenAI API key (required for openai backend)
+    /// OpenAI API key (required for openai backend, unless a custom
+    /// `openai_base_url` is set)
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub openai_key: Option<String>,
 
+    /// Base URL of an OpenAI-compatible server (e.g., "http://localhost:8080/v1")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub openai_base_url: Option<String>,
+
+    /// How the API key is sent (bearer or api-key)
+    #[serde(default, skip_serializing_if = "is_default_auth_style")]
+    pub openai_auth: AuthStyle,
+
+    /// OpenAI organization ID, sent as `OpenAI-Organization`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub openai_organization: Option<String>,
+
+    /// OpenAI project ID, sent as `OpenAI-Project`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub openai_project: Option<String>,
+
     /// Local whisper model to use (e.g., "base-q8", "small-q8", "large-v3-turbo-q5")
     /// Only used when backend is "local"
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub local_model: Option<String>,
 
+    /// Path to a custom GGML Whisper model file, used instead of `local_model`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_model_path: Option<PathBuf>,
+
+    /// Expected SHA1 checksum of `local_model_path`, checked at startup
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_model_sha1: Option<String>,
+
     /// Enable CoreML acceleration on macOS (uses Apple Neural Engine for ~3x faster encoding)
     /// Only used when backend is "local" on macOS
     #[serde(default = "default_true", skip_serializing_if = "is_true")]
     pub coreml: bool,
 
+    /// Minutes after which an unused local whisper model is unloaded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unload_after_idle: Option<f32>,
+
+    /// Number of recordings local whisper transcribes at once
+    #[serde(
+        default = "default_local_pool_size",
+        skip_serializing_if = "is_default_local_pool_size"
+    )]
+    pub local_pool_size: u32,
+
+    /// Marker starting each speaker turn with tinydiarize models, with
+    /// `{speaker}` replaced by 1 or 2
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speaker_marker: Option<String>,
+
+    /// Beam search width for local whisper, greedy decoding if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_beam_size: Option<u32>,
+
+    /// CPU threads used by local whisper
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_threads: Option<u32>,
+
+    /// Temperature added on each local whisper decoding fallback (0 disables)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_temperature_inc: Option<f32>,
+
+    /// Don't use the previous text as context for local whisper
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_no_context: Option<bool>,
+
+    /// Suppress blank output at the start of local whisper segments
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_suppress_blank: Option<bool>,
+
+    /// Suppress non-speech tokens like "[music]" in local whisper output
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_suppress_non_speech: Option<bool>,
+
+    /// Maximum local whisper segment length in characters (0 = no limit)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_max_len: Option<u32>,
+
+    /// Entropy threshold above which local whisper retries decoding
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_entropy_threshold: Option<f32>,
+
+    /// Average log probability below which local whisper retries decoding
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_logprob_threshold: Option<f32>,
+
+    /// Produce a single segment per local whisper window
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_single_segment: Option<bool>,
+
     /// Preferred language for transcription (ISO 639-1 code)
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub language: Option<String>,
 
+    /// Languages auto-detection may pick from (ISO 639-1 codes), all
+    /// languages if empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub languages: Vec<String>,
+
+    /// Prompt to guide transcription, e.g. product names or punctuation style
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+
+    /// Sampling temperature for transcription (0 to 1)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+
+    /// Transcribe, or translate to English
+    #[serde(default, skip_serializing_if = "is_default_task")]
+    pub task: Task,
+
     /// Model to use for OpenAI transcriptions
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub model: Option<String>,
@@ -102,6 +296,66 @@ pub struct Config {
     )]
     pub discard_duration: f32,
 
+    /// Discard recordings with less detected speech than this (in seconds)
+    #[serde(
+        default = "default_min_speech_duration",
+        skip_serializing_if = "is_default_min_speech_duration"
+    )]
+    pub min_speech_duration: f32,
+
+    /// Remove hallucinations and non-speech artifacts from transcripts
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub filter_output: bool,
+
+    /// Drop transcript segments more likely than this to be silence
+    #[serde(
+        default = "default_filter_no_speech_prob",
+        skip_serializing_if = "is_default_filter_no_speech_prob"
+    )]
+    pub filter_no_speech_prob: f32,
+
+    /// Drop transcript segments with a lower average log probability
+    #[serde(
+        default = "default_filter_min_logprob",
+        skip_serializing_if = "is_default_filter_min_logprob"
+    )]
+    pub filter_min_logprob: f32,
+
+    /// Extra phrases to drop when they make up a whole sentence
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub filter_blocklist: Vec<String>,
+
+    /// Normalize recording loudness before transcription (off, peak or loudness)
+    #[serde(default, skip_serializing_if = "is_default_normalization")]
+    pub normalize: Normalization,
+
+    /// Normalization target: dBFS for peak, LUFS for loudness.
+    /// Defaults to -1 dBFS (peak) or -16 LUFS (loudness).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normalize_target: Option<f32>,
+
+    /// Maximum gain (in dB) that normalization or AGC may apply
+    #[serde(
+        default = "default_max_gain_db",
+        skip_serializing_if = "is_default_max_gain_db"
+    )]
+    pub max_gain_db: f32,
+
+    /// Apply automatic gain control while recording
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub agc: bool,
+
+    /// Size (in MB) past which a recording is moved from memory to a temp file
+    #[serde(
+        default = "default_spill_threshold_mb",
+        skip_serializing_if = "is_default_spill_threshold_mb"
+    )]
+    pub spill_threshold_mb: u32,
+
+    /// Journal recordings to disk so they can be recovered after a crash
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub journal: bool,
+
     /// Number of retries for failed transcription requests
     #[serde(
         default = "default_retries",
@@ -109,10 +363,47 @@ pub struct Config {
     )]
     pub retries: u8,
 
+    /// Stop retrying once this many seconds have passed since the first attempt
+    #[serde(
+        default = "default_max_retry_duration",
+        skip_serializing_if = "is_default_max_retry_duration"
+    )]
+    pub max_retry_duration: f32,
+
+    /// Seconds allowed for each transcription attempt, with any backend
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transcription_timeout: Option<f32>,
+
+    /// Maximum number of transcription requests started per minute
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit_per_minute: Option<u32>,
+
+    /// Number of recent transcripts kept to answer repeated requests (0 = off)
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub cache_size: u32,
+
     /// Hotkey configuration (stored as string, parsed by app)
     /// Format: "modifier+modifier+key" e.g., "meta+shift+semicolon"
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub hotkey: Option<String>,
+
+    /// Seconds allowed to connect to the OpenAI API
+    #[serde(
+        default = "default_openai_connect_timeout",
+        skip_serializing_if = "is_default_openai_connect_timeout"
+    )]
+    pub openai_connect_timeout: f32,
+
+    /// Seconds allowed for a whole OpenAI request, including the upload
+    #[serde(
+        default = "default_openai_timeout",
+        skip_serializing_if = "is_default_openai_timeout"
+    )]
+    pub openai_timeout: f32,
+
+    /// Extra HTTP headers sent with every OpenAI request
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub openai_headers: BTreeMap<String, String>,
 }
 
 fn default_true() -> bool {
@@ -127,6 +418,10 @@ fn is_false(v: &bool) -> bool {
     !*v
 }
 
+fn is_zero(v: &u32) -> bool {
+    *v == 0
+}
+
 fn default_discard_duration() -> f32 {
     0.5
 }
@@ -135,6 +430,58 @@ fn is_default_discard_duration(v: &f32) -> bool {
     (*v - 0.5).abs() < f32::EPSILON
 }
 
+fn default_min_speech_duration() -> f32 {
+    0.3
+}
+
+fn is_default_min_speech_duration(v: &f32) -> bool {
+    (*v - 0.3).abs() < f32::EPSILON
+}
+
+fn default_filter_no_speech_prob() -> f32 {
+    0.8
+}
+
+fn is_default_filter_no_speech_prob(v: &f32) -> bool {
+    (*v - 0.8).abs() < f32::EPSILON
+}
+
+fn default_filter_min_logprob() -> f32 {
+    -1.5
+}
+
+fn is_default_filter_min_logprob(v: &f32) -> bool {
+    (*v + 1.5).abs() < f32::EPSILON
+}
+
+fn is_default_auth_style(v: &AuthStyle) -> bool {
+    *v == AuthStyle::Bearer
+}
+
+fn is_default_task(v: &Task) -> bool {
+    *v == Task::Transcribe
+}
+
+fn is_default_normalization(v: &Normalization) -> bool {
+    *v == Normalization::Off
+}
+
+fn default_max_gain_db() -> f32 {
+    30.0
+}
+
+fn is_default_max_gain_db(v: &f32) -> bool {
+    (*v - 30.0).abs() < f32::EPSILON
+}
+
+fn default_spill_threshold_mb() -> u32 {
+    64
+}
+
+fn is_default_spill_threshold_mb(v: &u32) -> bool {
+    *v == 64
+}
+
 fn default_retries() -> u8 {
     5
 }
@@ -143,27 +490,100 @@ fn is_default_retries(v: &u8) -> bool {
     *v == 5
 }
 
+fn default_openai_connect_timeout() -> f32 {
+    10.0
+}
+
+fn is_default_openai_connect_timeout(v: &f32) -> bool {
+    (*v - 10.0).abs() < f32::EPSILON
+}
+
+fn default_openai_timeout() -> f32 {
+    300.0
+}
+
+fn is_default_openai_timeout(v: &f32) -> bool {
+    (*v - 300.0).abs() < f32::EPSILON
+}
+
+fn default_local_pool_size() -> u32 {
+    1
+}
+
+fn is_default_local_pool_size(v: &u32) -> bool {
+    *v == 1
+}
+
+fn default_max_retry_duration() -> f32 {
+    60.0
+}
+
+fn is_default_max_retry_duration(v: &f32) -> bool {
+    (*v - 60.0).abs() < f32::EPSILON
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
-            backend: TranscriptionBackend::default(),
+            backend: default_backends(),
             openai_key: None,
+            openai_base_url: None,
+            openai_auth: AuthStyle::Bearer,
+            openai_organization: None,
+            openai_project: None,
             local_model: None,
+            local_model_path: None,
+            local_model_sha1: None,
             coreml: true,
+            unload_after_idle: None,
+            local_pool_size: default_local_pool_size(),
+            speaker_marker: None,
+            local_beam_size: None,
+            local_threads: None,
+            local_temperature_inc: None,
+            local_no_context: None,
+            local_suppress_blank: None,
+            local_suppress_non_speech: None,
+            local_max_len: None,
+            local_entropy_threshold: None,
+            local_logprob_threshold: None,
+            local_single_segment: None,
             language: None,
+            languages: Vec::new(),
+            prompt: None,
+            temperature: None,
+            task: Task::Transcribe,
             model: None,
             restore_clipboard: false,
             auto_paste: true,
             discard_duration: default_discard_duration(),
+            min_speech_duration: default_min_speech_duration(),
+            filter_output: true,
+            filter_no_speech_prob: default_filter_no_speech_prob(),
+            filter_min_logprob: default_filter_min_logprob(),
+            filter_blocklist: Vec::new(),
+            normalize: Normalization::Off,
+            normalize_target: None,
+            max_gain_db: default_max_gain_db(),
+            agc: false,
+            spill_threshold_mb: default_spill_threshold_mb(),
+            journal: true,
             retries: default_retries(),
+            max_retry_duration: default_max_retry_duration(),
+            transcription_timeout: None,
+            rate_limit_per_minute: None,
+            cache_size: 0,
             hotkey: None,
+            openai_connect_timeout: default_openai_connect_timeout(),
+            openai_timeout: default_openai_timeout(),
+            openai_headers: BTreeMap::new(),
         }
     }
 }
 
 impl Config {
-    /// Get the transcription backend
-    pub fn backend(&self) -> &TranscriptionBackend {
+    /// Get the transcription backends, in the order they are tried
+    pub fn backends(&self) -> &[TranscriptionBackend] {
         &self.backend
     }
 
@@ -182,11 +602,39 @@ impl Config {
         self.coreml
     }
 
+    /// Get the marker starting each speaker turn
+    pub fn speaker_marker(&self) -> &str {
+        self.speaker_marker
+            .as_deref()
+            .unwrap_or(DEFAULT_SPEAKER_MARKER)
+    }
+
+    /// Get how long the local model may stay unused before it is unloaded
+    pub fn unload_after_idle(&self) -> Option<Duration> {
+        self.unload_after_idle
+            .map(|minutes| Duration::from_secs_f32(minutes.max(0.0) * 60.0))
+    }
+
     /// Get the preferred language
     pub fn language(&self) -> Option<&str> {
         self.language.as_deref()
     }
 
+    /// Get the languages auto-detection may pick from
+    pub fn languages(&self) -> &[String] {
+        &self.languages
+    }
+
+    /// Get the transcription prompt
+    pub fn prompt(&self) -> Option<&str> {
+        self.prompt.as_deref()
+    }
+
+    /// Get the sampling temperature
+    pub fn temperature(&self) -> Option<f32> {
+        self.temperature
+    }
+
     /// Get the OpenAI model name
     pub fn model(&self) -> Option<&str> {
         self.model.as_deref()
@@ -196,6 +644,38 @@ impl Config {
     pub fn discard_duration(&self) -> Duration {
         Duration::from_secs_f32(self.discard_duration)
     }
+
+    /// Get the minimum speech duration as a Duration
+    pub fn min_speech_duration(&self) -> Duration {
+        Duration::from_secs_f32(self.min_speech_duration.max(0.0))
+    }
+
+    /// Get the retry time budget as a Duration
+    pub fn max_retry_duration(&self) -> Duration {
+        Duration::from_secs_f32(self.max_retry_duration.max(0.0))
+    }
+
+    /// Get the per-attempt transcription timeout as a Duration
+    pub fn transcription_timeout(&self) -> Option<Duration> {
+        self.transcription_timeout
+            .map(|secs| Duration::from_secs_f32(secs.max(0.0)))
+    }
+
+    /// Get the OpenAI connect timeout as a Duration
+    pub fn openai_connect_timeout(&self) -> Duration {
+        Duration::from_secs_f32(self.openai_connect_timeout.max(0.0))
+    }
+
+    /// Get the OpenAI request timeout as a Duration
+    pub fn openai_timeout(&self) -> Duration {
+        Duration::from_secs_f32(self.openai_timeout.max(0.0))
+    }
+
+    /// Get the normalization target, falling back to the mode's default
+    pub fn normalize_target(&self) -> f32 {
+        self.normalize_target
+            .unwrap_or_else(|| self.normalize.default_target())
+    }
 }
 
 /// Manages loading and saving configuration files.
@@ -235,7 +715,10 @@ impl ConfigManager {
         let config: Config = toml::from_str(&config_content)
             .with_context(|| format!("Failed to parse config file at {:?}", self.config_path))?;
 
-        if config.backend == TranscriptionBackend::OpenAI && config.key_openai().is_none() {
+        if config.backends().contains(&TranscriptionBackend::OpenAI)
+            && config.key_openai().is_none()
+            && config.openai_base_url.is_none()
+        {
             warn!(
                 "OpenAI API key is not set. Transcriptions will not work without it. \
                  Copy the config path via the tray icon to set the key."
@@ -283,6 +766,11 @@ mod tests {
         assert!(config.auto_paste);
         assert!(!config.restore_clipboard);
         assert_eq!(config.retries, 5);
+        assert_eq!(config.normalize, Normalization::Off);
+        assert!(!config.agc);
+        assert_eq!(config.spill_threshold_mb, 64);
+        assert!(config.journal);
+        assert_eq!(config.min_speech_duration, 0.3);
     }
 
     #[test]
@@ -290,14 +778,37 @@ mod tests {
         let config = Config {
             openai_key: Some("test-key".to_string()),
             model: Some("whisper-1".to_string()),
+            openai_auth: AuthStyle::ApiKey,
+            openai_headers: BTreeMap::from([("X-Team".to_string(), "audio".to_string())]),
             ..Default::default()
         };
 
         let serialized = toml::to_string_pretty(&config).unwrap();
         let deserialized: Config = toml::from_str(&serialized).unwrap();
 
+        assert!(serialized.contains(r#"openai_auth = "api-key""#));
         assert_eq!(config.openai_key, deserialized.openai_key);
         assert_eq!(config.model, deserialized.model);
+        assert_eq!(config.openai_auth, deserialized.openai_auth);
+        assert_eq!(config.openai_headers, deserialized.openai_headers);
+    }
+
+    #[test]
+    fn test_backend_one_or_many() {
+        let one: Config = toml::from_str(r#"backend = "openai""#).unwrap();
+        let many: Config = toml::from_str(r#"backend = ["openai", "local"]"#).unwrap();
+
+        assert_eq!(one.backends(), [TranscriptionBackend::OpenAI]);
+        assert_eq!(
+            many.backends(),
+            [TranscriptionBackend::OpenAI, TranscriptionBackend::Local]
+        );
+        assert!(
+            toml::to_string(&many)
+                .unwrap()
+                .contains(r#"backend = ["openai", "local"]"#)
+        );
+        assert!(toml::from_str::<Config>("backend = []").is_err());
     }
 
     #[test]
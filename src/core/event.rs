@@ -12,6 +12,17 @@ pub enum AudioEvent {
     StateChanged(MicState),
 }
 
+/// Events emitted by transcription backends that load a model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelEvent {
+    /// The model started loading
+    Loading,
+    /// The model finished loading, or failed to
+    Loaded,
+    /// The model was unloaded after being idle
+    Unloaded,
+}
+
 /// State tracked during recording for UI updates.
 #[derive(Debug, Clone, Default)]
 pub struct RecordingState {
@@ -7,8 +7,11 @@ mod config;
 mod event;
 mod state;
 
-pub use config::{Config, ConfigManager, TranscriptionBackend, default_data_dir, models_dir};
-pub use event::{AudioEvent, RecordingState};
+pub use config::{
+    AuthStyle, Config, ConfigManager, Normalization, Task, TranscriptionBackend, default_data_dir,
+    models_dir,
+};
+pub use event::{AudioEvent, ModelEvent, RecordingState};
 pub use state::MicState;
 
 /// Application name
@@ -7,6 +7,8 @@ pub enum MicState {
     Activating,
     /// Actively recording audio
     Active,
+    /// Recording is paused, samples are not being written
+    Paused,
     /// Idle, not recording
     Idle,
     /// Processing recorded audio (transcribing)
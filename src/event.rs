@@ -1,6 +1,8 @@
 //! Application events for the tao event loop.
 
-use crate::MicState;
+use bytes::Bytes;
+
+use crate::{MicState, Transcript};
 
 /// Events for the tao event loop, extending the core AudioEvent.
 #[derive(Debug, Clone)]
@@ -8,9 +10,19 @@ pub enum WhispEvent {
     /// The microphone state has changed
     StateChanged(MicState),
     /// A transcription is ready
-    TranscriptReady(String),
+    TranscriptReady(Transcript),
     /// Transcription failed after retries
-    TranscriptionFailed(Vec<u8>),
+    TranscriptionFailed(Bytes),
+    /// Transcription was cancelled by the user
+    TranscriptionCancelled,
+    /// The transcript held only hallucinations or non-speech artifacts
+    TranscriptFiltered,
+    /// The transcription model started loading
+    ModelLoading,
+    /// The transcription model finished loading, or failed to
+    ModelLoaded,
+    /// The transcription model was unloaded after being idle
+    ModelUnloaded,
     /// An error occurred during audio processing
     AudioError(Vec<u8>),
 }
@@ -18,7 +18,14 @@ fn load_color(color: Color) -> Icon {
 static IDLE: LazyLock<Icon> = LazyLock::new(|| load_color(color::WHITE));
 static WAITING: LazyLock<Icon> = LazyLock::new(|| load_color(color::YELLOW));
 static ACTIVE: LazyLock<Icon> = LazyLock::new(|| load_color(color::GREEN));
+static PAUSED: LazyLock<Icon> = LazyLock::new(|| load_color(color::BLUE));
 static WORKING: LazyLock<Icon> = LazyLock::new(|| load_color(color::YELLOW));
+static LOADING: LazyLock<Icon> = LazyLock::new(|| load_color(color::PURPLE));
+
+/// Tray icon shown while the transcription model loads.
+pub fn loading_icon() -> Icon {
+    LOADING.clone()
+}
 
 /// Extension trait to get icons for MicState.
 pub trait MicStateIcon {
@@ -31,6 +38,7 @@ impl MicStateIcon for MicState {
         match self {
             MicState::Activating => WAITING.clone(),
             MicState::Active => ACTIVE.clone(),
+            MicState::Paused => PAUSED.clone(),
             MicState::Idle => IDLE.clone(),
             MicState::Processing => WORKING.clone(),
         }
@@ -5,17 +5,25 @@ pub mod transcribe;
 
 // Re-exports
 pub use core::{
-    APP_NAME, APP_NAME_PRETTY, AudioEvent, Config, ConfigManager, DEFAULT_LOG_LEVEL, MicState,
-    RecordingState, TranscriptionBackend,
+    APP_NAME, APP_NAME_PRETTY, AudioEvent, AuthStyle, Config, ConfigManager, DEFAULT_LOG_LEVEL,
+    MicState, ModelEvent, Normalization, RecordingState, Task, TranscriptionBackend,
 };
 
-pub use audio::{Recorder, RecorderError, Recording, RecordingHandle};
+pub use audio::{
+    AudioAnalysis, AutoGainConfig, NormalizeConfig, NormalizeOutcome, Recorder, RecorderError,
+    Recording, RecordingHandle, SpillConfig, journal_dir, orphaned_journals,
+};
+pub use transcribe::{
+    ApiError, AudioChunk, CancellationToken, FallbackTranscriber, MetricsSnapshot, OpenAIClient,
+    OpenAIConfig, OutputFilter, ResponseFormat, RetryPolicy, Segment, StreamSegment,
+    TimestampGranularity, TranscribeError, TranscribeOptions, Transcriber, TranscriberExt,
+    TranscriberMetrics, Transcript, Word,
+};
 #[cfg(feature = "local-whisper")]
 pub use transcribe::{
-    LocalWhisperClient, LocalWhisperConfig, WhisperModel, download_model, ensure_model,
-    model_exists, model_path,
+    DecodingParams, LocalWhisperClient, LocalWhisperConfig, ModelHeader, WhisperModel,
+    download_model, ensure_model, model_exists, model_path, verify_model_file,
 };
-pub use transcribe::{OpenAIClient, OpenAIConfig, TranscribeError, Transcriber};
 
 // App-specific modules
 mod color;
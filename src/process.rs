@@ -3,8 +3,9 @@
 //! This module handles the async processing of recorded audio,
 //! including transcription and result delivery.
 
-use std::sync::{Arc, RwLock};
-use std::time::Instant;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
 
 use bytes::Bytes;
 use tao::event_loop::EventLoopProxy;
@@ -13,31 +14,51 @@ use tokio::sync::mpsc;
 use tracing::{error, info, warn};
 
 use crate::event::WhispEvent;
-use crate::{Config, Recording, Transcriber};
+use crate::transcribe::layer;
+use crate::{
+    CancellationToken, Config, MetricsSnapshot, NormalizeConfig, NormalizeOutcome, OutputFilter,
+    Recording, TranscribeError, TranscribeOptions, Transcriber, TranscriberMetrics, Transcript,
+};
 
 /// Processing pipeline for audio data.
 pub struct AudioPipeline {
     runtime: Runtime,
     config: Arc<RwLock<Config>>,
     transcriber: Arc<dyn Transcriber>,
+    metrics: Arc<TranscriberMetrics>,
     transcription_handles: mpsc::UnboundedSender<TranscriptionTask>,
+    jobs: Arc<Mutex<Jobs>>,
+}
+
+/// Cancellation tokens of queued and running transcriptions.
+#[derive(Default)]
+struct Jobs {
+    next_id: u64,
+    tokens: HashMap<u64, CancellationToken>,
 }
 
 type TranscriptionTask = tokio::task::JoinHandle<TranscriptionResult>;
 
 /// Result of submitting audio to the pipeline.
 pub enum SubmitResult {
-    /// Audio was sent for processing
-    Sent,
+    /// Audio was sent for processing, the token cancels it
+    Sent(CancellationToken),
     /// Audio was discarded (too short)
     Discarded,
+    /// Audio was discarded because it held less speech than
+    /// `min_speech_duration`, with the speech that was detected
+    NoSpeech(Duration),
 }
 
 impl AudioPipeline {
     /// Create a new pipeline instance.
+    ///
+    /// The backends are chained in the order given and wrapped in the
+    /// retry, timeout and other layers set in the config, see
+    /// [`layer::from_config`].
     pub fn new(
         config: Arc<RwLock<Config>>,
-        transcriber: Arc<dyn Transcriber>,
+        backends: Vec<Arc<dyn Transcriber>>,
         event_sender: EventLoopProxy<WhispEvent>,
     ) -> anyhow::Result<Self> {
         let runtime = tokio::runtime::Builder::new_multi_thread()
@@ -45,7 +66,10 @@ impl AudioPipeline {
             .enable_all()
             .build()?;
 
-        let transcription_handles = start_results_collector(&runtime, event_sender)?;
+        let transcription_handles = start_results_collector(&runtime, event_sender.clone())?;
+
+        let metrics = Arc::new(TranscriberMetrics::default());
+        let transcriber = layer::from_config(backends, &config.read().unwrap(), metrics.clone());
 
         info!(
             transcriber = transcriber.name(),
@@ -56,17 +80,35 @@ impl AudioPipeline {
             runtime,
             config,
             transcriber,
+            metrics,
             transcription_handles,
+            jobs: Arc::default(),
         })
     }
 
+    /// Warm up the transcriber in the background, see
+    /// [`Transcriber::warm_up`].
+    ///
+    /// Backends that load a model report it as [`ModelEvent`](crate::ModelEvent)s themselves.
+    /// Transcriptions submitted meanwhile wait for it to finish.
+    pub fn warm_up(&self) {
+        let transcriber = self.transcriber.clone();
+        self.runtime.spawn(async move {
+            // Failures are logged by the tracing layer, and the first
+            // transcription tries again
+            transcriber.warm_up().await.ok();
+        });
+    }
+
     /// Submit audio for processing.
     pub fn submit(&self, recording: Recording) -> anyhow::Result<SubmitResult> {
         info!(
-            samples = recording.samples(),
+            frames = recording.frames(),
+            channels = recording.channels(),
             bytes = recording.data().len(),
             bytes_mb = recording.data().len() as f64 / (1024.0 * 1024.0),
             length_seconds = recording.duration().as_secs_f64(),
+            dropped_samples = recording.dropped_samples(),
             "audio submitted"
         );
 
@@ -75,76 +117,170 @@ impl AudioPipeline {
                 discard_duration = ?self.config.read().unwrap().discard_duration(),
                 "discarding recording"
             );
+            recording.discard();
             return Ok(SubmitResult::Discarded);
         }
 
+        let min_speech = self.config.read().unwrap().min_speech_duration();
+        if let Some(speech) = lacks_speech(&recording, min_speech) {
+            info!(
+                speech = ?speech,
+                min_speech_duration = ?min_speech,
+                "discarding recording without speech"
+            );
+            recording.discard();
+            return Ok(SubmitResult::NoSpeech(speech));
+        }
+
+        let cancellation = CancellationToken::new();
+        let id = {
+            let mut jobs = self.jobs.lock().unwrap();
+            let id = jobs.next_id;
+            jobs.next_id += 1;
+            jobs.tokens.insert(id, cancellation.clone());
+            id
+        };
+
         let transcriber = self.transcriber.clone();
         let config = self.config.clone();
-        let handle = self
-            .runtime
-            .spawn(transcribe(transcriber, config, recording));
+        let jobs = self.jobs.clone();
+        let token = cancellation.clone();
+        let handle = self.runtime.spawn(async move {
+            let result = transcribe(transcriber, config, recording, token).await;
+            jobs.lock().unwrap().tokens.remove(&id);
+            result
+        });
 
         self.transcription_handles.send(handle)?;
-        Ok(SubmitResult::Sent)
+        Ok(SubmitResult::Sent(cancellation))
+    }
+
+    /// Counters of the transcriptions run so far.
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.metrics.snapshot()
+    }
+
+    /// Cancel all queued and running transcriptions.
+    ///
+    /// Returns the number of transcriptions cancelled.
+    pub fn cancel_all(&self) -> usize {
+        let tokens: Vec<CancellationToken> = self
+            .jobs
+            .lock()
+            .unwrap()
+            .tokens
+            .drain()
+            .map(|(_, token)| token)
+            .collect();
+        for token in &tokens {
+            token.cancel();
+        }
+        info!(count = tokens.len(), "cancelled transcriptions");
+        tokens.len()
+    }
+}
+
+/// Measure the speech in a recording, returning it when it is less than
+/// `min_speech`.
+///
+/// Spilled recordings are long enough that reading them back would stall
+/// the caller, and are sent as they are.
+fn lacks_speech(recording: &Recording, min_speech: Duration) -> Option<Duration> {
+    if min_speech.is_zero() || recording.spill_path().is_some() {
+        return None;
+    }
+    match recording.analyze() {
+        Ok(analysis) if analysis.speech_duration() < min_speech => Some(analysis.speech_duration()),
+        Ok(analysis) => {
+            info!(speech = ?analysis.speech_duration(), "speech detected");
+            None
+        }
+        // Better to transcribe than to lose a dictation
+        Err(e) => {
+            warn!("Failed to analyze recording, transcribing anyway: {}", e);
+            None
+        }
     }
 }
 
 async fn transcribe(
     transcriber: Arc<dyn Transcriber>,
     config: Arc<RwLock<Config>>,
-    recording: Recording,
+    mut recording: Recording,
+    cancellation: CancellationToken,
 ) -> TranscriptionResult {
-    // Bytes is reference-counted, so cloning is O(1)
-    let audio: Bytes = recording.into_data().into();
-    let num_bytes = audio.len();
-
-    let (mut num_retries, language) = {
-        let config_read = config.read().unwrap();
-        (
-            config_read.retries,
-            config_read.language().map(|s| s.to_string()),
-        )
-    };
-
-    let mut before = Instant::now();
-    let mut result = transcriber
-        .transcribe(audio.clone(), language.as_deref())
-        .await;
-
-    while result.is_err() && num_retries > 0 {
-        warn!("Retrying transcription, previous error: {:?}", result);
-        before = Instant::now();
-        result = transcriber
-            .transcribe(audio.clone(), language.as_deref())
-            .await;
-        num_retries -= 1;
+    let normalize = NormalizeConfig::from_config(&config.read().unwrap());
+    if let Some(normalize) = normalize {
+        // Decoding and re-encoding is CPU-bound, keep the runtime's worker
+        // free for the results collector meanwhile
+        match tokio::task::block_in_place(|| recording.normalize(&normalize)) {
+            Ok(NormalizeOutcome::Applied(applied)) => info!(
+                mode = ?normalize.mode,
+                measured = applied.measured,
+                gain_db = applied.gain_db,
+                "normalized recording"
+            ),
+            Ok(NormalizeOutcome::TooQuiet) => {
+                info!(mode = ?normalize.mode, "recording too quiet to normalize")
+            }
+            Ok(NormalizeOutcome::Spilled) => {
+                info!(mode = ?normalize.mode, "normalization skipped: recording spilled to disk")
+            }
+            Err(e) => warn!("Failed to normalize recording, transcribing as is: {}", e),
+        }
     }
 
-    let Ok(text) = result else {
-        return TranscriptionResult::RetryError {
-            retries: config.read().unwrap().retries,
-            error: anyhow::anyhow!("Transcription failed"),
-            data: audio.to_vec(),
-        };
-    };
+    // Bytes is reference-counted, so cloning is O(1)
+    let audio = recording.bytes();
+    let options =
+        TranscribeOptions::from_config(&config.read().unwrap()).with_cancellation(cancellation);
+    let filter = OutputFilter::from_config(&config.read().unwrap());
 
-    let duration = before.elapsed();
-    let mb_per_second = num_bytes as f64 / (1024.0 * 1024.0) / duration.as_secs_f64();
-    info!(
-        duration = ?duration,
-        mb_per_second = mb_per_second,
-        "transcription completed"
-    );
+    let transcript = match transcriber.transcribe(audio.clone(), &options).await {
+        Ok(transcript) => transcript,
+        Err(TranscribeError::Cancelled) => {
+            drop(audio);
+            recording.discard();
+            return TranscriptionResult::Cancelled;
+        }
+        Err(error) => {
+            if let Some(path) = recording.spill_path() {
+                warn!(path = ?path, "Keeping spilled recording of failed transcription");
+            }
+            if let Some(path) = recording.journal_path() {
+                info!(path = ?path, "Keeping journal of failed transcription for recovery");
+            }
+            return TranscriptionResult::RetryError {
+                error: error.into(),
+                data: audio,
+            };
+        }
+    };
+    drop(audio);
+    recording.discard();
 
-    TranscriptionResult::Success(text)
+    let Some(filter) = filter else {
+        return TranscriptionResult::Success(transcript);
+    };
+    let original = transcript.text.clone();
+    let transcript = filter.apply(transcript);
+    if transcript.text != original {
+        info!(original, filtered = transcript.text, "filtered transcript");
+    }
+    if transcript.is_empty() && !original.trim().is_empty() {
+        return TranscriptionResult::Filtered;
+    }
+    TranscriptionResult::Success(transcript)
 }
 
 enum TranscriptionResult {
-    Success(String),
+    Success(Transcript),
+    /// Nothing was left after filtering
+    Filtered,
+    Cancelled,
     RetryError {
-        retries: u8,
         error: anyhow::Error,
-        data: Vec<u8>,
+        data: Bytes,
     },
 }
 
@@ -157,21 +293,24 @@ fn start_results_collector(
     runtime.spawn(async move {
         while let Some(task) = task_receiver.recv().await {
             match task.await {
-                Ok(TranscriptionResult::Success(text)) => {
-                    info!("Transcription: {}", text);
+                Ok(TranscriptionResult::Success(transcript)) => {
+                    info!("Transcription: {}", transcript.text);
+                    event_sender
+                        .send_event(WhispEvent::TranscriptReady(transcript))
+                        .ok();
+                }
+                Ok(TranscriptionResult::Filtered) => {
+                    warn!("Transcript held only silence or filler, nothing to paste");
+                    event_sender.send_event(WhispEvent::TranscriptFiltered).ok();
+                }
+                Ok(TranscriptionResult::Cancelled) => {
+                    info!("Transcription cancelled");
                     event_sender
-                        .send_event(WhispEvent::TranscriptReady(text))
+                        .send_event(WhispEvent::TranscriptionCancelled)
                         .ok();
                 }
-                Ok(TranscriptionResult::RetryError {
-                    retries,
-                    error,
-                    data,
-                }) => {
-                    error!(
-                        "Transcription failed after {} retries: {:?}",
-                        retries, error
-                    );
+                Ok(TranscriptionResult::RetryError { error, data }) => {
+                    error!("Transcription failed: {:?}", error);
                     event_sender
                         .send_event(WhispEvent::TranscriptionFailed(data))
                         .ok();
@@ -187,3 +326,24 @@ fn start_results_collector(
 
     Ok(task_sender)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::tests::{clicks, float_spec, wav};
+
+    #[test]
+    fn test_lacks_speech_in_clicks() {
+        let recording = wav(float_spec(1, 16000), &clicks(16000, 2.0));
+        assert_eq!(
+            lacks_speech(&recording, Duration::from_millis(300)),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn test_lacks_speech_disabled() {
+        let recording = wav(float_spec(1, 16000), &clicks(16000, 2.0));
+        assert_eq!(lacks_speech(&recording, Duration::ZERO), None);
+    }
+}
@@ -0,0 +1,206 @@
+//! Decoding parameters for local Whisper transcription.
+
+use anyhow::{Result, bail};
+use whisper_rs::{FullParams, SamplingStrategy};
+
+use crate::core::Config;
+
+/// Largest beam whisper.cpp supports (`WHISPER_MAX_DECODERS`).
+const MAX_BEAM_SIZE: u32 = 8;
+
+/// Tuning of whisper.cpp's decoder.
+///
+/// Every field is optional; `None` keeps the whisper.cpp default, shown in
+/// parentheses below.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DecodingParams {
+    /// Beam search width (greedy decoding). Beams of 2 to 5 are more
+    /// accurate, at the cost of speed.
+    pub beam_size: Option<u32>,
+    /// CPU threads (up to 4)
+    pub threads: Option<u32>,
+    /// Temperature added when decoding fails the entropy or log-prob
+    /// threshold and is retried (0.2). 0 disables the fallback.
+    pub temperature_inc: Option<f32>,
+    /// Don't condition each window on the text of the previous one (true)
+    pub no_context: Option<bool>,
+    /// Suppress blank output at the start of a segment (true)
+    pub suppress_blank: Option<bool>,
+    /// Suppress non-speech tokens like `[music]` or `(laughs)` (false)
+    pub suppress_non_speech: Option<bool>,
+    /// Maximum segment length in characters, 0 for no limit (0)
+    pub max_len: Option<u32>,
+    /// Compression threshold above which decoding is retried, catches
+    /// repetition loops (2.4)
+    pub entropy_threshold: Option<f32>,
+    /// Average log probability below which decoding is retried (-1.0)
+    pub logprob_threshold: Option<f32>,
+    /// Produce a single segment per window (false)
+    pub single_segment: Option<bool>,
+}
+
+impl DecodingParams {
+    /// Take the `local_*` decoding settings from the app config.
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            beam_size: config.local_beam_size,
+            threads: config.local_threads,
+            temperature_inc: config.local_temperature_inc,
+            no_context: config.local_no_context,
+            suppress_blank: config.local_suppress_blank,
+            suppress_non_speech: config.local_suppress_non_speech,
+            max_len: config.local_max_len,
+            entropy_threshold: config.local_entropy_threshold,
+            logprob_threshold: config.local_logprob_threshold,
+            single_segment: config.local_single_segment,
+        }
+    }
+
+    /// Check that the values are in a range whisper.cpp can use.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(beam_size) = self.beam_size
+            && !(1..=MAX_BEAM_SIZE).contains(&beam_size)
+        {
+            bail!("Beam size must be between 1 and {MAX_BEAM_SIZE}, got {beam_size}");
+        }
+        if self.threads == Some(0) {
+            bail!("Thread count must be at least 1");
+        }
+        if let Some(inc) = self.temperature_inc
+            && !(0.0..=1.0).contains(&inc)
+        {
+            bail!("Temperature increment must be between 0 and 1, got {inc}");
+        }
+        if let Some(threshold) = self.entropy_threshold
+            && !(threshold.is_finite() && threshold > 0.0)
+        {
+            bail!("Entropy threshold must be positive, got {threshold}");
+        }
+        if let Some(threshold) = self.logprob_threshold
+            && !(threshold.is_finite() && threshold <= 0.0)
+        {
+            bail!("Log-prob threshold must be 0 or negative, got {threshold}");
+        }
+        Ok(())
+    }
+
+    /// Sampling strategy: beam search if a beam size is set, else greedy.
+    pub(crate) fn strategy(&self) -> SamplingStrategy {
+        match self.beam_size {
+            Some(beam_size) => SamplingStrategy::BeamSearch {
+                beam_size: beam_size as i32,
+                patience: -1.0,
+            },
+            None => SamplingStrategy::Greedy { best_of: 1 },
+        }
+    }
+
+    /// CPU threads to use, whisper.cpp's default if unset.
+    pub(crate) fn threads(&self) -> usize {
+        match self.threads {
+            Some(threads) => threads as usize,
+            None => std::thread::available_parallelism().map_or(1, |n| n.get().min(4)),
+        }
+    }
+
+    /// Apply the settings that are set to `params`.
+    pub(crate) fn apply(&self, params: &mut FullParams<'_, '_>) {
+        if let Some(threads) = self.threads {
+            params.set_n_threads(threads as i32);
+        }
+        if let Some(inc) = self.temperature_inc {
+            params.set_temperature_inc(inc);
+        }
+        if let Some(no_context) = self.no_context {
+            params.set_no_context(no_context);
+        }
+        if let Some(suppress_blank) = self.suppress_blank {
+            params.set_suppress_blank(suppress_blank);
+        }
+        if let Some(suppress_non_speech) = self.suppress_non_speech {
+            params.set_suppress_nst(suppress_non_speech);
+        }
+        if let Some(max_len) = self.max_len.filter(|&len| len > 0) {
+            // Segments are only split using token timestamps
+            params.set_max_len(max_len as i32);
+            params.set_token_timestamps(true);
+            params.set_split_on_word(true);
+        }
+        if let Some(threshold) = self.entropy_threshold {
+            params.set_entropy_thold(threshold);
+        }
+        if let Some(threshold) = self.logprob_threshold {
+            params.set_logprob_thold(threshold);
+        }
+        if let Some(single_segment) = self.single_segment {
+            params.set_single_segment(single_segment);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        assert!(DecodingParams::default().validate().is_ok());
+
+        let valid = DecodingParams {
+            beam_size: Some(5),
+            threads: Some(8),
+            temperature_inc: Some(0.0),
+            entropy_threshold: Some(2.8),
+            logprob_threshold: Some(-0.5),
+            ..Default::default()
+        };
+        assert!(valid.validate().is_ok());
+
+        for invalid in [
+            DecodingParams {
+                beam_size: Some(9),
+                ..Default::default()
+            },
+            DecodingParams {
+                threads: Some(0),
+                ..Default::default()
+            },
+            DecodingParams {
+                temperature_inc: Some(-0.2),
+                ..Default::default()
+            },
+            DecodingParams {
+                entropy_threshold: Some(f32::NAN),
+                ..Default::default()
+            },
+            DecodingParams {
+                logprob_threshold: Some(1.0),
+                ..Default::default()
+            },
+        ] {
+            assert!(invalid.validate().is_err(), "{invalid:?}");
+        }
+    }
+
+    #[test]
+    fn test_from_config() {
+        let config: Config = toml::from_str(
+            r#"
+            local_beam_size = 5
+            local_no_context = false
+            local_logprob_threshold = -0.8
+            "#,
+        )
+        .unwrap();
+        let params = DecodingParams::from_config(&config);
+
+        assert_eq!(params.beam_size, Some(5));
+        assert_eq!(params.no_context, Some(false));
+        assert_eq!(params.logprob_threshold, Some(-0.8));
+        assert_eq!(params.threads, None);
+        assert!(matches!(
+            params.strategy(),
+            SamplingStrategy::BeamSearch { beam_size: 5, .. }
+        ));
+    }
+}
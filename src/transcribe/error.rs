@@ -0,0 +1,174 @@
+//! Errors returned by transcription APIs.
+
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+use reqwest::StatusCode;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use serde::Deserialize;
+
+/// An error response from a transcription API.
+#[derive(Debug, Clone)]
+pub struct ApiError {
+    /// HTTP status of the response
+    pub status: StatusCode,
+    /// Machine-readable error code, e.g. `invalid_api_key`
+    pub code: Option<String>,
+    /// Error message, or the raw body if it isn't a JSON error
+    pub message: String,
+    /// Response headers
+    pub headers: HeaderMap,
+}
+
+/// OpenAI-style error body: `{"error": {"message": ..., "code": ...}}`.
+#[derive(Debug, Deserialize)]
+struct ErrorBody {
+    error: ErrorDetails,
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorDetails {
+    message: String,
+    #[serde(default, rename = "type")]
+    kind: Option<String>,
+    #[serde(default)]
+    code: Option<String>,
+}
+
+impl ApiError {
+    /// Build an error from a response's status, headers and body.
+    pub fn new(status: StatusCode, headers: HeaderMap, body: &str) -> Self {
+        let (code, message) = match serde_json::from_str::<ErrorBody>(body) {
+            Ok(ErrorBody { error }) => (error.code.or(error.kind), error.message),
+            Err(_) => (None, body.trim().to_string()),
+        };
+        Self {
+            status,
+            code,
+            message,
+            headers,
+        }
+    }
+
+    /// Read an error from a failed response.
+    pub async fn from_response(response: reqwest::Response) -> Self {
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.text().await.unwrap_or_default();
+        Self::new(status, headers, &body)
+    }
+
+    /// Whether the request may succeed if sent again.
+    ///
+    /// Rate limits, timeouts and server errors are transient. An exhausted
+    /// quota is reported as a rate limit but won't go away by retrying.
+    pub fn is_retryable(&self) -> bool {
+        match self.status {
+            StatusCode::TOO_MANY_REQUESTS => self.code.as_deref() != Some("insufficient_quota"),
+            StatusCode::REQUEST_TIMEOUT | StatusCode::CONFLICT => true,
+            status => status.is_server_error(),
+        }
+    }
+
+    /// How long the server asked us to wait before retrying.
+    ///
+    /// Understands OpenAI's `retry-after-ms` as well as `Retry-After` in
+    /// seconds or as an HTTP date.
+    pub fn retry_after(&self) -> Option<Duration> {
+        let header = |name| self.headers.get(name)?.to_str().ok();
+
+        if let Some(millis) = header("retry-after-ms").and_then(|v| v.parse::<f64>().ok()) {
+            return Some(Duration::from_secs_f64(millis.max(0.0) / 1000.0));
+        }
+        let value = header(RETRY_AFTER.as_str())?;
+        if let Ok(secs) = value.parse::<f64>() {
+            return Some(Duration::from_secs_f64(secs.max(0.0)));
+        }
+        let date = httpdate::parse_http_date(value).ok()?;
+        Some(
+            date.duration_since(SystemTime::now())
+                .unwrap_or(Duration::ZERO),
+        )
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "API returned {}", self.status)?;
+        if let Some(code) = &self.code {
+            write!(f, " ({})", code)?;
+        }
+        if !self.message.is_empty() {
+            write!(f, ": {}", self.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    fn error(status: u16, headers: &[(&'static str, &str)], body: &str) -> ApiError {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        ApiError::new(StatusCode::from_u16(status).unwrap(), map, body)
+    }
+
+    #[test]
+    fn test_parses_openai_error_body() {
+        let err = error(
+            401,
+            &[],
+            r#"{"error": {"message": "Incorrect API key provided", "type": "invalid_request_error", "code": "invalid_api_key"}}"#,
+        );
+
+        assert_eq!(err.code.as_deref(), Some("invalid_api_key"));
+        assert_eq!(
+            err.to_string(),
+            "API returned 401 Unauthorized (invalid_api_key): Incorrect API key provided"
+        );
+        assert!(!err.is_retryable());
+        assert_eq!(error(502, &[], "Bad gateway").message, "Bad gateway");
+    }
+
+    #[test]
+    fn test_retryable_statuses() {
+        assert!(error(429, &[], "").is_retryable());
+        assert!(error(500, &[], "").is_retryable());
+        assert!(error(503, &[], "").is_retryable());
+        assert!(!error(400, &[], "").is_retryable());
+        assert!(
+            !error(
+                429,
+                &[],
+                r#"{"error": {"message": "Quota exceeded", "code": "insufficient_quota"}}"#,
+            )
+            .is_retryable()
+        );
+    }
+
+    #[test]
+    fn test_retry_after() {
+        assert_eq!(
+            error(429, &[("retry-after", "3")], "").retry_after(),
+            Some(Duration::from_secs(3))
+        );
+        assert_eq!(
+            error(429, &[("retry-after-ms", "250"), ("retry-after", "1")], "").retry_after(),
+            Some(Duration::from_millis(250))
+        );
+        let past = httpdate::fmt_http_date(SystemTime::UNIX_EPOCH);
+        assert_eq!(
+            error(429, &[("retry-after", &past)], "").retry_after(),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(error(429, &[], "").retry_after(), None);
+    }
+}
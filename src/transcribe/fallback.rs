@@ -0,0 +1,168 @@
+//! Transcription with fallback backends.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use tracing::{info, warn};
+
+use super::{Bytes, Result, TranscribeError, TranscribeOptions, Transcriber, Transcript};
+
+/// Transcriber that tries an ordered list of backends until one succeeds.
+///
+/// Useful to prefer a cloud backend and fall back to a local model when
+/// offline, or the reverse. The [`Transcript::backend`] of the result names
+/// the backend that produced it.
+pub struct FallbackTranscriber {
+    backends: Vec<Arc<dyn Transcriber>>,
+    name: String,
+}
+
+impl FallbackTranscriber {
+    /// Create a fallback chain, tried in the given order.
+    pub fn new(backends: Vec<Arc<dyn Transcriber>>) -> Self {
+        let names: Vec<&str> = backends.iter().map(|b| b.name()).collect();
+        let name = format!("fallback({})", names.join(", "));
+        Self { backends, name }
+    }
+
+    /// The backends in the order they are tried.
+    pub fn backends(&self) -> &[Arc<dyn Transcriber>] {
+        &self.backends
+    }
+}
+
+#[async_trait]
+impl Transcriber for FallbackTranscriber {
+    /// Transcribe with the first backend that succeeds.
+    ///
+    /// Returns the error of the last backend if all of them fail. A
+    /// cancelled request isn't passed on to the next backend.
+    async fn transcribe(&self, audio: Bytes, options: &TranscribeOptions) -> Result<Transcript> {
+        let mut last_error = None;
+        for (i, backend) in self.backends.iter().enumerate() {
+            match backend.transcribe(audio.clone(), options).await {
+                Ok(transcript) => {
+                    if i > 0 {
+                        info!(
+                            backend = backend.name(),
+                            "Transcribed with fallback backend"
+                        );
+                    }
+                    return Ok(transcript);
+                }
+                Err(TranscribeError::Cancelled) => return Err(TranscribeError::Cancelled),
+                Err(e) => {
+                    warn!(backend = backend.name(), "Backend failed: {}", e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| {
+            TranscribeError::TranscriptionFailed("No transcription backend configured".to_string())
+        }))
+    }
+
+    /// Warm up every backend, so falling back is fast too.
+    ///
+    /// Returns the first error, after trying all backends.
+    async fn warm_up(&self) -> Result<()> {
+        let mut first_error = None;
+        for backend in &self.backends {
+            if let Err(e) = backend.warm_up().await {
+                warn!(backend = backend.name(), "Warming up backend failed: {}", e);
+                first_error.get_or_insert(e);
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    /// Backend that fails with a fixed error, or succeeds if there is none.
+    struct Fixed {
+        name: &'static str,
+        error: Option<fn() -> TranscribeError>,
+        calls: AtomicUsize,
+    }
+
+    impl Fixed {
+        fn new(name: &'static str, error: Option<fn() -> TranscribeError>) -> Arc<Self> {
+            Arc::new(Self {
+                name,
+                error,
+                calls: AtomicUsize::new(0),
+            })
+        }
+    }
+
+    #[async_trait]
+    impl Transcriber for Fixed {
+        async fn transcribe(
+            &self,
+            _audio: Bytes,
+            _options: &TranscribeOptions,
+        ) -> Result<Transcript> {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            match self.error {
+                Some(error) => Err(error()),
+                None => Ok(Transcript::new("hello", self.name)),
+            }
+        }
+
+        fn name(&self) -> &str {
+            self.name
+        }
+    }
+
+    fn offline() -> TranscribeError {
+        TranscribeError::TranscriptionFailed("offline".to_string())
+    }
+
+    #[tokio::test]
+    async fn test_falls_back_in_order() {
+        let cloud = Fixed::new("cloud", Some(offline));
+        let local = Fixed::new("local", None);
+        let unused = Fixed::new("unused", None);
+        let fallback = FallbackTranscriber::new(vec![cloud.clone(), local.clone(), unused.clone()]);
+
+        let transcript = fallback
+            .transcribe(Bytes::new(), &TranscribeOptions::default())
+            .await
+            .unwrap();
+
+        assert_eq!(transcript.backend, "local");
+        assert_eq!(fallback.name(), "fallback(cloud, local, unused)");
+        assert_eq!(unused.calls.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn test_stops_on_cancel_and_reports_last_error() {
+        let cancelled = FallbackTranscriber::new(vec![
+            Fixed::new("a", Some(|| TranscribeError::Cancelled)),
+            Fixed::new("b", None),
+        ]);
+        let failing = FallbackTranscriber::new(vec![
+            Fixed::new("a", Some(|| TranscribeError::NoApiKey)),
+            Fixed::new("b", Some(offline)),
+        ]);
+        let options = TranscribeOptions::default();
+
+        let cancelled = cancelled.transcribe(Bytes::new(), &options).await;
+        let failing = failing.transcribe(Bytes::new(), &options).await;
+
+        assert!(matches!(cancelled, Err(TranscribeError::Cancelled)));
+        assert!(matches!(
+            failing,
+            Err(TranscribeError::TranscriptionFailed(_))
+        ));
+    }
+}
@@ -0,0 +1,336 @@
+//! Removal of hallucinations and non-speech artifacts from transcripts.
+//!
+//! On silent or near-silent audio Whisper tends to invent text: closing
+//! lines of the videos it was trained on ("Thank you for watching."),
+//! annotations like `[BLANK_AUDIO]` or `(music)`, or the same phrase over
+//! and over. [`OutputFilter`] removes these before the text is pasted.
+
+use std::collections::HashSet;
+
+use crate::core::Config;
+use crate::transcribe::{Segment, Transcript};
+
+/// Phrases Whisper is known to produce on silence, normalized.
+const BUILTIN_BLOCKLIST: &[&str] = &[
+    "thank you for watching",
+    "thanks for watching",
+    "thank you very much for watching",
+    "thank you so much for watching",
+    "please subscribe to my channel",
+    "don't forget to like and subscribe",
+    "subtitles by the amara org community",
+    "see you in the next video",
+];
+
+/// Last words of parenthesized annotations Whisper emits for non-speech
+/// sounds, like `(upbeat music)` or `(audience laughing)`.
+const NON_SPEECH_TAGS: &[&str] = &[
+    "music",
+    "applause",
+    "laughter",
+    "laughing",
+    "laughs",
+    "chuckles",
+    "cheering",
+    "clapping",
+    "silence",
+    "inaudible",
+    "indistinct",
+    "noise",
+    "static",
+    "coughing",
+    "coughs",
+    "sighs",
+    "breathing",
+    "crosstalk",
+    "beeping",
+];
+
+/// Times a phrase may repeat in a row before it counts as runaway
+/// repetition.
+const MAX_REPEATS: usize = 3;
+
+/// Longest phrase, in words, checked for runaway repetition.
+///
+/// Single words aren't checked, "very very very very" is emphasis.
+const MAX_REPEATED_WORDS: usize = 10;
+
+/// Cleans transcripts of hallucinations and non-speech artifacts.
+///
+/// In order, it:
+/// - drops segments with a high no-speech probability or a low average
+///   log-probability, where the backend reports them,
+/// - strips annotations in square brackets, parenthesized non-speech tags
+///   like `(upbeat music)` and music notes,
+/// - drops sentences that match the blocklist,
+/// - collapses a phrase of two or more words repeated more than three
+///   times in a row to one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputFilter {
+    /// Segments more likely than this to contain no speech are dropped
+    pub max_no_speech_prob: f32,
+    /// Segments with a lower average log-probability are dropped
+    pub min_avg_logprob: f32,
+    /// Normalized phrases dropped when they make up a whole sentence
+    pub blocklist: HashSet<String>,
+}
+
+impl Default for OutputFilter {
+    fn default() -> Self {
+        Self {
+            max_no_speech_prob: Config::default().filter_no_speech_prob,
+            min_avg_logprob: Config::default().filter_min_logprob,
+            blocklist: BUILTIN_BLOCKLIST.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+}
+
+impl OutputFilter {
+    /// Build the filter from the app config.
+    ///
+    /// Returns `None` when filtering is turned off.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        if !config.filter_output {
+            return None;
+        }
+        Some(
+            Self {
+                max_no_speech_prob: config.filter_no_speech_prob,
+                min_avg_logprob: config.filter_min_logprob,
+                ..Self::default()
+            }
+            .with_blocklist(&config.filter_blocklist),
+        )
+    }
+
+    /// Add phrases to the blocklist.
+    pub fn with_blocklist(mut self, phrases: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        self.blocklist
+            .extend(phrases.into_iter().map(|p| normalize(p.as_ref())));
+        self
+    }
+
+    /// Filter `transcript`, leaving it empty if nothing but artifacts
+    /// remain.
+    pub fn apply(&self, mut transcript: Transcript) -> Transcript {
+        if transcript.segments.is_empty() {
+            transcript.text = transcript
+                .text
+                .lines()
+                .map(|line| self.clean(line))
+                .filter(|line| !line.is_empty())
+                .collect::<Vec<_>>()
+                .join("\n");
+            return transcript;
+        }
+
+        let mut kept: Vec<Segment> = Vec::with_capacity(transcript.segments.len());
+        for mut segment in std::mem::take(&mut transcript.segments) {
+            let text = self.clean(&segment.text);
+            let key = normalize(&text);
+            let repeated = kept
+                .iter()
+                .rev()
+                .take(MAX_REPEATS)
+                .filter(|s| normalize(&s.text) == key)
+                .count()
+                == MAX_REPEATS;
+            if !self.is_speech(&segment) || text.is_empty() || repeated {
+                // Keep the speaker change the dropped segment ends with
+                if segment.speaker_turn
+                    && let Some(previous) = kept.last_mut()
+                {
+                    previous.speaker_turn = true;
+                }
+                continue;
+            }
+            if segment.text.starts_with(char::is_whitespace) {
+                segment.text = format!(" {text}");
+            } else {
+                segment.text = text;
+            }
+            kept.push(segment);
+        }
+        transcript.segments = kept;
+        transcript.rebuild_text();
+        transcript
+    }
+
+    /// Whether a segment's probabilities, if known, suggest speech.
+    fn is_speech(&self, segment: &Segment) -> bool {
+        segment
+            .no_speech_prob
+            .is_none_or(|p| p <= self.max_no_speech_prob)
+            && segment
+                .avg_logprob
+                .is_none_or(|p| p >= self.min_avg_logprob)
+    }
+
+    /// Clean a line of text.
+    fn clean(&self, text: &str) -> String {
+        let text = strip_annotations(text);
+        let words: Vec<&str> = sentences(&text)
+            .filter(|sentence| !self.blocklist.contains(&normalize(sentence)))
+            .flat_map(str::split_whitespace)
+            .collect();
+        collapse_repetitions(&words).join(" ")
+    }
+}
+
+/// Lowercase words without punctuation, separated by single spaces.
+fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .replace(|c: char| !c.is_alphanumeric() && c != '\'', " ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Remove non-speech annotations: anything in square brackets,
+/// [`NON_SPEECH_TAGS`] in parentheses and music notes.
+fn strip_annotations(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let close = match c {
+            '[' => rest.find(']'),
+            '(' => rest.find(')').filter(|&end| {
+                normalize(&rest[1..end])
+                    .rsplit(' ')
+                    .next()
+                    .is_some_and(|last| NON_SPEECH_TAGS.contains(&last))
+            }),
+            _ => None,
+        };
+        match close {
+            Some(end) => rest = &rest[end + 1..],
+            None => {
+                if c != '♪' && c != '♫' {
+                    result.push(c);
+                }
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    result
+}
+
+/// Split text after sentence-ending punctuation.
+fn sentences(text: &str) -> impl Iterator<Item = &str> {
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        if rest.trim().is_empty() {
+            return None;
+        }
+        let mut chars = rest.char_indices().peekable();
+        let mut end = rest.len();
+        while let Some((i, c)) = chars.next() {
+            if matches!(c, '.' | '!' | '?' | '…')
+                && chars.peek().is_none_or(|(_, next)| next.is_whitespace())
+            {
+                end = i + c.len_utf8();
+                break;
+            }
+        }
+        let (sentence, remainder) = rest.split_at(end);
+        rest = remainder;
+        Some(sentence)
+    })
+}
+
+/// Keep a single copy of any phrase of two or more words repeated more
+/// than [`MAX_REPEATS`] times in a row.
+fn collapse_repetitions<'a>(words: &[&'a str]) -> Vec<&'a str> {
+    let keys: Vec<String> = words.iter().map(|w| normalize(w)).collect();
+    let mut result = Vec::with_capacity(words.len());
+    let mut i = 0;
+    'words: while i < words.len() {
+        for len in 2..=MAX_REPEATED_WORDS.min(words.len() - i) {
+            let phrase = &keys[i..i + len];
+            let repeats = keys[i..]
+                .chunks_exact(len)
+                .take_while(|chunk| *chunk == phrase)
+                .count();
+            if repeats > MAX_REPEATS {
+                result.extend_from_slice(&words[i..i + len]);
+                i += repeats * len;
+                continue 'words;
+            }
+        }
+        result.push(words[i]);
+        i += 1;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn segment(text: &str, no_speech_prob: f32, avg_logprob: f32) -> Segment {
+        Segment {
+            no_speech_prob: Some(no_speech_prob),
+            avg_logprob: Some(avg_logprob),
+            ..Segment::new(text, Duration::ZERO, Duration::from_secs(1))
+        }
+    }
+
+    #[test]
+    fn test_cleans_text() {
+        let filter = OutputFilter::default().with_blocklist(["Thank you."]);
+        let clean = |text| filter.apply(Transcript::new(text, "test")).text;
+
+        assert_eq!(clean("[BLANK_AUDIO]"), "");
+        assert_eq!(clean("♪ (upbeat music) ♪"), "");
+        assert_eq!(clean("(Applause) Welcome back."), "Welcome back.");
+        assert_eq!(
+            clean("It ships Friday (probably), notes (see attached)."),
+            "It ships Friday (probably), notes (see attached)."
+        );
+        assert_eq!(clean("Thank you for watching!"), "");
+        assert_eq!(clean("Ship it. Thanks for watching."), "Ship it.");
+        assert_eq!(
+            clean("Call me (Alex) at 5 [inaudible] o'clock."),
+            "Call me (Alex) at 5 o'clock."
+        );
+        assert_eq!(clean("I think I think I think I think so."), "I think so.");
+        assert_eq!(clean("No, no, no."), "No, no, no.");
+        assert_eq!(
+            clean("That was very very very very good."),
+            "That was very very very very good."
+        );
+        assert_eq!(
+            clean("Thank you. Thank you for the review."),
+            "Thank you for the review."
+        );
+        assert_eq!(clean("— Hi.\n— Thank you."), "— Hi.");
+    }
+
+    #[test]
+    fn test_drops_unlikely_segments() {
+        let mut hallucination = segment(" Thank you.", 0.95, -0.3);
+        hallucination.speaker_turn = true;
+        let transcript = Transcript::new("", "test")
+            .with_segments(vec![
+                segment(" Deploy on Friday.", 0.01, -0.2),
+                segment(" Deploy on Friday.", 0.01, -0.2),
+                hallucination,
+                segment(" Okay.", 0.02, -0.3),
+                segment(" Okay.", 0.02, -0.3),
+                segment(" Okay.", 0.02, -0.3),
+                segment(" Okay.", 0.02, -0.3),
+                segment(" Mumble.", 0.3, -2.1),
+            ])
+            .with_speaker_markers("- ");
+
+        let filtered = OutputFilter::default().apply(transcript);
+
+        assert_eq!(
+            filtered.text,
+            "- Deploy on Friday. Deploy on Friday.\n- Okay. Okay. Okay."
+        );
+        assert_eq!(filtered.segments.len(), 5);
+    }
+}
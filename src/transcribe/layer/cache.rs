@@ -0,0 +1,151 @@
+//! Cache of finished transcriptions.
+
+use std::collections::VecDeque;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use tracing::debug;
+
+use crate::core::Task;
+use crate::transcribe::{
+    AudioStream, Bytes, Result, SegmentStream, TranscribeOptions, Transcriber, Transcript,
+};
+
+/// Identifies a request by a hash of its audio and the options that change
+/// the result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CacheKey {
+    audio_hash: u64,
+    audio_len: usize,
+    language: Option<String>,
+    languages: Vec<String>,
+    prompt: Option<String>,
+    temperature: Option<u32>,
+    task: Task,
+}
+
+impl CacheKey {
+    fn new(audio: &[u8], options: &TranscribeOptions) -> Self {
+        let mut hasher = DefaultHasher::new();
+        audio.hash(&mut hasher);
+        Self {
+            audio_hash: hasher.finish(),
+            audio_len: audio.len(),
+            language: options.language.clone(),
+            languages: options.languages.clone(),
+            prompt: options.prompt.clone(),
+            temperature: options.temperature.map(f32::to_bits),
+            task: options.task,
+        }
+    }
+}
+
+/// Returns the earlier transcript when the same audio is transcribed again
+/// with the same options, e.g. when a failed recording is resubmitted.
+///
+/// Keeps the `capacity` most recent transcripts. Failures aren't cached.
+pub struct Cache<T> {
+    inner: T,
+    capacity: usize,
+    /// Most recently used last
+    entries: Mutex<VecDeque<(CacheKey, Transcript)>>,
+}
+
+impl<T> Cache<T> {
+    /// Wrap `inner`, keeping up to `capacity` transcripts.
+    pub fn new(inner: T, capacity: usize) -> Self {
+        Self {
+            inner,
+            capacity,
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    fn get(&self, key: &CacheKey) -> Option<Transcript> {
+        let mut entries = self.entries.lock().unwrap();
+        let index = entries.iter().position(|(k, _)| k == key)?;
+        let entry = entries.remove(index)?;
+        let transcript = entry.1.clone();
+        entries.push_back(entry);
+        Some(transcript)
+    }
+
+    fn insert(&self, key: CacheKey, transcript: Transcript) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back((key, transcript));
+    }
+}
+
+#[async_trait]
+impl<T: Transcriber> Transcriber for Cache<T> {
+    async fn transcribe(&self, audio: Bytes, options: &TranscribeOptions) -> Result<Transcript> {
+        let key = CacheKey::new(&audio, options);
+        if let Some(transcript) = self.get(&key) {
+            debug!(audio_bytes = key.audio_len, "Using cached transcript");
+            return Ok(transcript);
+        }
+
+        let transcript = self.inner.transcribe(audio, options).await?;
+        self.insert(key, transcript.clone());
+        Ok(transcript)
+    }
+
+    /// Streams aren't cached, the audio isn't known until the stream ends.
+    fn transcribe_stream<'a>(
+        &'a self,
+        audio: AudioStream,
+        options: &'a TranscribeOptions,
+    ) -> SegmentStream<'a> {
+        self.inner.transcribe_stream(audio, options)
+    }
+
+    async fn warm_up(&self) -> Result<()> {
+        self.inner.warm_up().await
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use super::*;
+    use crate::transcribe::layer::tests::Scripted;
+
+    #[tokio::test]
+    async fn test_reuses_transcript_for_same_audio_and_options() {
+        let inner = Scripted::ok("hello");
+        let calls = inner.calls.clone();
+        let cache = Cache::new(inner, 1);
+        let english = TranscribeOptions::default().with_language("en");
+
+        for _ in 0..2 {
+            cache
+                .transcribe(Bytes::from_static(b"one"), &english)
+                .await
+                .unwrap();
+        }
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        // Other options miss, and push the first entry out of the cache
+        cache
+            .transcribe(Bytes::from_static(b"one"), &TranscribeOptions::default())
+            .await
+            .unwrap();
+        cache
+            .transcribe(Bytes::from_static(b"one"), &english)
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+    }
+}
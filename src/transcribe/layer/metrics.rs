@@ -0,0 +1,146 @@
+//! Counters for transcription requests.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+
+use crate::transcribe::{
+    AudioStream, Bytes, Result, SegmentStream, TranscribeError, TranscribeOptions, Transcriber,
+    Transcript,
+};
+
+/// Counters shared between a [`Metrics`] layer and its readers.
+#[derive(Debug, Default)]
+pub struct TranscriberMetrics {
+    requests: AtomicU64,
+    failures: AtomicU64,
+    cancelled: AtomicU64,
+    audio_bytes: AtomicU64,
+    busy_micros: AtomicU64,
+}
+
+/// A point-in-time copy of [`TranscriberMetrics`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    /// Transcriptions started
+    pub requests: u64,
+    /// Transcriptions that failed, not counting cancelled ones
+    pub failures: u64,
+    /// Transcriptions cancelled before they finished
+    pub cancelled: u64,
+    /// Audio sent for transcription
+    pub audio_bytes: u64,
+    /// Total time spent in finished transcriptions
+    pub busy: Duration,
+}
+
+impl TranscriberMetrics {
+    /// Read the current counters.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            requests: self.requests.load(Ordering::Relaxed),
+            failures: self.failures.load(Ordering::Relaxed),
+            cancelled: self.cancelled.load(Ordering::Relaxed),
+            audio_bytes: self.audio_bytes.load(Ordering::Relaxed),
+            busy: Duration::from_micros(self.busy_micros.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+impl MetricsSnapshot {
+    /// Transcriptions that succeeded.
+    pub fn successes(&self) -> u64 {
+        self.requests.saturating_sub(self.failures + self.cancelled)
+    }
+}
+
+/// Counts requests, failures, audio and time spent in the inner transcriber.
+pub struct Metrics<T> {
+    inner: T,
+    metrics: Arc<TranscriberMetrics>,
+}
+
+impl<T> Metrics<T> {
+    /// Wrap `inner`, counting into `metrics`.
+    pub fn new(inner: T, metrics: Arc<TranscriberMetrics>) -> Self {
+        Self { inner, metrics }
+    }
+
+    /// The counters of this layer.
+    pub fn metrics(&self) -> &Arc<TranscriberMetrics> {
+        &self.metrics
+    }
+}
+
+#[async_trait]
+impl<T: Transcriber> Transcriber for Metrics<T> {
+    async fn transcribe(&self, audio: Bytes, options: &TranscribeOptions) -> Result<Transcript> {
+        let metrics = &self.metrics;
+        metrics.requests.fetch_add(1, Ordering::Relaxed);
+        metrics
+            .audio_bytes
+            .fetch_add(audio.len() as u64, Ordering::Relaxed);
+
+        let start = Instant::now();
+        let result = self.inner.transcribe(audio, options).await;
+        metrics
+            .busy_micros
+            .fetch_add(start.elapsed().as_micros() as u64, Ordering::Relaxed);
+        match &result {
+            Ok(_) => {}
+            Err(TranscribeError::Cancelled) => {
+                metrics.cancelled.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(_) => {
+                metrics.failures.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        result
+    }
+
+    fn transcribe_stream<'a>(
+        &'a self,
+        audio: AudioStream,
+        options: &'a TranscribeOptions,
+    ) -> SegmentStream<'a> {
+        self.inner.transcribe_stream(audio, options)
+    }
+
+    async fn warm_up(&self) -> Result<()> {
+        self.inner.warm_up().await
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcribe::layer::tests::Scripted;
+
+    #[tokio::test]
+    async fn test_counts_outcomes() {
+        let inner = Scripted::ok("hello")
+            .with_errors(vec![TranscribeError::NoApiKey, TranscribeError::Cancelled]);
+        let metrics = Metrics::new(inner, Arc::default());
+        let options = TranscribeOptions::default();
+
+        for _ in 0..3 {
+            metrics
+                .transcribe(Bytes::from_static(b"RIFF"), &options)
+                .await
+                .ok();
+        }
+
+        let snapshot = metrics.metrics().snapshot();
+        assert_eq!(snapshot.requests, 3);
+        assert_eq!(snapshot.failures, 1);
+        assert_eq!(snapshot.cancelled, 1);
+        assert_eq!(snapshot.successes(), 1);
+        assert_eq!(snapshot.audio_bytes, 12);
+    }
+}
@@ -0,0 +1,243 @@
+//! Middleware layers around a [`Transcriber`].
+//!
+//! Each layer wraps another transcriber and is a transcriber itself, so
+//! layers stack in any order:
+//!
+//! ```ignore
+//! let transcriber = client
+//!     .timeout(Duration::from_secs(60))
+//!     .retry(RetryPolicy::new(3, Duration::from_secs(60)))
+//!     .traced();
+//! ```
+//!
+//! [`from_config`] builds the stack the app uses.
+
+mod cache;
+mod metrics;
+mod rate_limit;
+mod retry;
+mod timeout;
+mod trace;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+pub use cache::Cache;
+pub use metrics::{Metrics, MetricsSnapshot, TranscriberMetrics};
+pub use rate_limit::RateLimit;
+pub use retry::{Retry, RetryPolicy};
+pub use timeout::Timeout;
+pub use trace::Tracing;
+use tracing::info;
+
+use crate::core::Config;
+use crate::transcribe::{FallbackTranscriber, Transcriber};
+
+/// Builder methods to wrap a [`Transcriber`] in layers.
+pub trait TranscriberExt: Transcriber + Sized {
+    /// Retry transient failures, see [`Retry`].
+    fn retry(self, policy: RetryPolicy) -> Retry<Self> {
+        Retry::new(self, policy)
+    }
+
+    /// Fail transcriptions that take longer than `timeout`, see [`Timeout`].
+    fn timeout(self, timeout: Duration) -> Timeout<Self> {
+        Timeout::new(self, timeout)
+    }
+
+    /// Start at most `max_requests` per `period`, see [`RateLimit`].
+    fn rate_limit(self, max_requests: u32, period: Duration) -> RateLimit<Self> {
+        RateLimit::new(self, max_requests, period)
+    }
+
+    /// Count requests into `metrics`, see [`Metrics`].
+    fn metrics(self, metrics: Arc<TranscriberMetrics>) -> Metrics<Self> {
+        Metrics::new(self, metrics)
+    }
+
+    /// Reuse the last `capacity` transcripts, see [`Cache`].
+    fn cache(self, capacity: usize) -> Cache<Self> {
+        Cache::new(self, capacity)
+    }
+
+    /// Log each transcription, see [`Tracing`].
+    fn traced(self) -> Tracing<Self> {
+        Tracing::new(self)
+    }
+}
+
+impl<T: Transcriber> TranscriberExt for T {}
+
+/// Wrap backends in the layers configured in `config`.
+///
+/// Each backend gets its own retry, rate limit and timeout, from the
+/// outside in, so the rate limit and timeout apply to each attempt at one
+/// backend and a stalled backend hands off to the next in time. Several
+/// backends are chained in a [`FallbackTranscriber`], tried in order.
+/// Tracing, metrics and cache wrap the whole chain, and cache hits don't
+/// count against the rate limit.
+pub fn from_config(
+    backends: Vec<Arc<dyn Transcriber>>,
+    config: &Config,
+    metrics: Arc<TranscriberMetrics>,
+) -> Arc<dyn Transcriber> {
+    let mut backends: Vec<Arc<dyn Transcriber>> = backends
+        .into_iter()
+        .map(|backend| backend_from_config(backend, config))
+        .collect();
+    let mut transcriber = if backends.len() == 1 {
+        backends.remove(0)
+    } else {
+        let fallback = FallbackTranscriber::new(backends);
+        info!(backends = fallback.name(), "Using fallback backends");
+        Arc::new(fallback)
+    };
+    if config.cache_size > 0 {
+        transcriber = Arc::new(transcriber.cache(config.cache_size as usize));
+    }
+    Arc::new(transcriber.metrics(metrics).traced())
+}
+
+/// Wrap one backend in the retry, rate limit and timeout set in `config`.
+fn backend_from_config(inner: Arc<dyn Transcriber>, config: &Config) -> Arc<dyn Transcriber> {
+    let mut transcriber = inner;
+    if let Some(timeout) = config.transcription_timeout() {
+        transcriber = Arc::new(transcriber.timeout(timeout));
+    }
+    if let Some(per_minute) = config.rate_limit_per_minute {
+        transcriber = Arc::new(transcriber.rate_limit(per_minute, Duration::from_secs(60)));
+    }
+    Arc::new(transcriber.retry(RetryPolicy::from_config(config)))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::transcribe::{
+        Bytes, CancellationToken, Result, TranscribeError, TranscribeOptions, Transcript,
+    };
+
+    /// Transcriber that fails with the given errors in order, then succeeds.
+    pub(crate) struct Scripted {
+        text: String,
+        errors: Mutex<Vec<TranscribeError>>,
+        delay: Duration,
+        /// Number of calls to `transcribe`
+        pub calls: Arc<AtomicUsize>,
+        /// Cancellation token of the last call
+        pub cancelled: Arc<Mutex<Option<CancellationToken>>>,
+    }
+
+    impl Scripted {
+        pub fn ok(text: &str) -> Self {
+            Self {
+                text: text.to_string(),
+                errors: Mutex::default(),
+                delay: Duration::ZERO,
+                calls: Arc::default(),
+                cancelled: Arc::default(),
+            }
+        }
+
+        pub fn with_errors(mut self, mut errors: Vec<TranscribeError>) -> Self {
+            errors.reverse();
+            self.errors = Mutex::new(errors);
+            self
+        }
+
+        pub fn with_delay(mut self, delay: Duration) -> Self {
+            self.delay = delay;
+            self
+        }
+    }
+
+    #[async_trait]
+    impl Transcriber for Scripted {
+        async fn transcribe(
+            &self,
+            _audio: Bytes,
+            options: &TranscribeOptions,
+        ) -> Result<Transcript> {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            *self.cancelled.lock().unwrap() = Some(options.cancellation.clone());
+            tokio::time::sleep(self.delay).await;
+
+            let error = self.errors.lock().unwrap().pop();
+            match error {
+                Some(error) => Err(error),
+                None => Ok(Transcript::new(self.text.clone(), "scripted")),
+            }
+        }
+
+        fn name(&self) -> &str {
+            "scripted"
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_from_config_retries_timeouts_and_caches() {
+        let inner = Scripted::ok("hello").with_delay(Duration::from_secs(2));
+        let calls = inner.calls.clone();
+        let config = Config {
+            transcription_timeout: Some(1.0),
+            retries: 1,
+            cache_size: 4,
+            ..Config::default()
+        };
+        let metrics = Arc::new(TranscriberMetrics::default());
+        let transcriber = from_config(vec![Arc::new(inner)], &config, metrics.clone());
+        let options = TranscribeOptions::default();
+
+        let result = transcriber.transcribe(Bytes::new(), &options).await;
+        assert!(matches!(result, Err(TranscribeError::TimedOut(_))));
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+
+        let config = Config {
+            cache_size: 4,
+            ..Config::default()
+        };
+        let transcriber = from_config(
+            vec![Arc::new(Scripted::ok("hello"))],
+            &config,
+            metrics.clone(),
+        );
+        for _ in 0..2 {
+            let transcript = transcriber.transcribe(Bytes::new(), &options).await;
+            assert_eq!(transcript.unwrap().text, "hello");
+        }
+
+        let snapshot = metrics.snapshot();
+        assert_eq!((snapshot.requests, snapshot.failures), (3, 1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_from_config_times_out_each_backend() {
+        let slow = Scripted::ok("remote").with_delay(Duration::from_secs(60));
+        let slow_calls = slow.calls.clone();
+        let fast = Scripted::ok("local");
+        let fast_calls = fast.calls.clone();
+        let config = Config {
+            transcription_timeout: Some(5.0),
+            retries: 1,
+            ..Config::default()
+        };
+        let metrics = Arc::new(TranscriberMetrics::default());
+        let transcriber = from_config(vec![Arc::new(slow), Arc::new(fast)], &config, metrics);
+
+        let started = tokio::time::Instant::now();
+        let transcript = transcriber
+            .transcribe(Bytes::new(), &TranscribeOptions::default())
+            .await
+            .unwrap();
+
+        assert_eq!(transcript.text, "local");
+        assert_eq!(slow_calls.load(Ordering::Relaxed), 2);
+        assert_eq!(fast_calls.load(Ordering::Relaxed), 1);
+        assert!(started.elapsed() < Duration::from_secs(60));
+    }
+}
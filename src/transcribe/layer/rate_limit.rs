@@ -0,0 +1,112 @@
+//! Limit on the rate of transcription requests.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use tracing::debug;
+
+use crate::transcribe::{
+    AudioStream, Bytes, Result, SegmentStream, TranscribeError, TranscribeOptions, Transcriber,
+    Transcript,
+};
+
+/// Holds back transcriptions so at most `max_requests` start per `period`.
+///
+/// Requests over the limit wait for a free slot rather than failing.
+pub struct RateLimit<T> {
+    inner: T,
+    max_requests: usize,
+    period: Duration,
+    /// Start times of the requests in the current window, oldest first
+    started: Mutex<VecDeque<Instant>>,
+}
+
+impl<T> RateLimit<T> {
+    /// Wrap `inner`, starting at most `max_requests` per `period`.
+    pub fn new(inner: T, max_requests: u32, period: Duration) -> Self {
+        Self {
+            inner,
+            max_requests: max_requests.max(1) as usize,
+            period,
+            started: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Wait for a free slot and claim it.
+    async fn acquire(&self) {
+        // Holding the lock while waiting keeps waiting requests in order
+        let mut started = self.started.lock().await;
+        loop {
+            let now = Instant::now();
+            while started
+                .front()
+                .is_some_and(|&start| now.duration_since(start) >= self.period)
+            {
+                started.pop_front();
+            }
+            if started.len() < self.max_requests {
+                started.push_back(now);
+                return;
+            }
+
+            let free_at = started[0] + self.period;
+            debug!(wait = ?free_at.duration_since(now), "Rate limit reached, waiting");
+            tokio::time::sleep_until(free_at).await;
+        }
+    }
+}
+
+#[async_trait]
+impl<T: Transcriber> Transcriber for RateLimit<T> {
+    async fn transcribe(&self, audio: Bytes, options: &TranscribeOptions) -> Result<Transcript> {
+        if options
+            .cancellation
+            .run_until_cancelled(self.acquire())
+            .await
+            .is_none()
+        {
+            return Err(TranscribeError::Cancelled);
+        }
+        self.inner.transcribe(audio, options).await
+    }
+
+    /// Streams aren't held back, they can't wait without losing audio.
+    fn transcribe_stream<'a>(
+        &'a self,
+        audio: AudioStream,
+        options: &'a TranscribeOptions,
+    ) -> SegmentStream<'a> {
+        self.inner.transcribe_stream(audio, options)
+    }
+
+    async fn warm_up(&self) -> Result<()> {
+        self.inner.warm_up().await
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcribe::layer::tests::Scripted;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_spaces_out_requests() {
+        let limit = RateLimit::new(Scripted::ok("hello"), 2, Duration::from_secs(10));
+        let options = TranscribeOptions::default();
+        let start = Instant::now();
+
+        for _ in 0..5 {
+            limit.transcribe(Bytes::new(), &options).await.unwrap();
+        }
+
+        // Two requests at 0s, two at 10s, one at 20s
+        assert_eq!(start.elapsed(), Duration::from_secs(20));
+    }
+}
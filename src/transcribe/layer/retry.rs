@@ -0,0 +1,250 @@
+//! Retrying failed transcriptions.
+//!
+//! Only transient failures are retried, see
+//! [`TranscribeError::is_retryable`]. Retries back off exponentially with
+//! jitter, so a rate-limited server isn't hammered by every client at once,
+//! unless the server says how long to wait with `Retry-After`.
+
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use tracing::warn;
+
+use crate::core::Config;
+use crate::transcribe::{
+    AudioStream, Bytes, Result, SegmentStream, TranscribeError, TranscribeOptions, Transcriber,
+    Transcript,
+};
+
+/// Delay before the first retry, doubled for each one after.
+const BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// Upper bound on the backoff delay.
+const MAX_DELAY: Duration = Duration::from_secs(16);
+
+/// When and how often to retry a failed transcription.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Maximum number of retries after the first attempt
+    pub max_retries: u32,
+    /// Total time after which no more retries are started
+    pub max_duration: Duration,
+}
+
+impl RetryPolicy {
+    /// Create a retry policy.
+    pub fn new(max_retries: u32, max_duration: Duration) -> Self {
+        Self {
+            max_retries,
+            max_duration,
+        }
+    }
+
+    /// Take the retry settings from the app config.
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(config.retries.into(), config.max_retry_duration())
+    }
+
+    /// Delay before the given retry (0 for the first), or `None` to give up.
+    ///
+    /// Gives up if the error isn't transient, the retries are used up or
+    /// the retry would start after `max_duration` has passed since the
+    /// first attempt.
+    pub fn delay(
+        &self,
+        retry: u32,
+        error: &TranscribeError,
+        elapsed: Duration,
+    ) -> Option<Duration> {
+        if retry >= self.max_retries || !error.is_retryable() {
+            return None;
+        }
+        let delay = error
+            .retry_after()
+            .unwrap_or_else(|| jitter(backoff(retry)));
+        (elapsed + delay <= self.max_duration).then_some(delay)
+    }
+
+    /// Run `attempt` until it succeeds or the policy gives up.
+    ///
+    /// Returns the result of the last attempt and the number of retries.
+    pub async fn run<T, F, Fut>(&self, mut attempt: F) -> (Result<T>, u32)
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let start = Instant::now();
+        let mut retry = 0;
+        loop {
+            let error = match attempt().await {
+                Ok(value) => return (Ok(value), retry),
+                Err(e) => e,
+            };
+            let Some(delay) = self.delay(retry, &error, start.elapsed()) else {
+                return (Err(error), retry);
+            };
+
+            warn!(
+                retry = retry + 1,
+                delay = ?delay,
+                "Retrying transcription, previous error: {}", error
+            );
+            tokio::time::sleep(delay).await;
+            retry += 1;
+        }
+    }
+}
+
+/// Retries failed transcriptions of the inner transcriber.
+///
+/// Waiting between retries stops as soon as the request is cancelled.
+pub struct Retry<T> {
+    inner: T,
+    policy: RetryPolicy,
+}
+
+impl<T> Retry<T> {
+    /// Wrap `inner`, retrying according to `policy`.
+    pub fn new(inner: T, policy: RetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+}
+
+#[async_trait]
+impl<T: Transcriber> Transcriber for Retry<T> {
+    async fn transcribe(&self, audio: Bytes, options: &TranscribeOptions) -> Result<Transcript> {
+        let (result, _) = options
+            .cancellation
+            .run_until_cancelled(
+                self.policy
+                    .run(|| self.inner.transcribe(audio.clone(), options)),
+            )
+            .await
+            .unwrap_or((Err(TranscribeError::Cancelled), 0));
+        result
+    }
+
+    /// Streams aren't retried, a failed stream can't be replayed.
+    fn transcribe_stream<'a>(
+        &'a self,
+        audio: AudioStream,
+        options: &'a TranscribeOptions,
+    ) -> SegmentStream<'a> {
+        self.inner.transcribe_stream(audio, options)
+    }
+
+    async fn warm_up(&self) -> Result<()> {
+        self.inner.warm_up().await
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+}
+
+/// Exponential backoff delay for the given retry.
+fn backoff(retry: u32) -> Duration {
+    BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(retry))
+        .min(MAX_DELAY)
+}
+
+/// Randomize a delay to between half and all of it.
+fn jitter(delay: Duration) -> Duration {
+    delay.mul_f64(0.5 + fastrand::f64() / 2.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::StatusCode;
+    use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+
+    use super::*;
+    use crate::transcribe::ApiError;
+
+    fn api_error(status: StatusCode, retry_after: Option<&str>) -> TranscribeError {
+        let mut headers = HeaderMap::new();
+        if let Some(value) = retry_after {
+            headers.insert(RETRY_AFTER, HeaderValue::from_str(value).unwrap());
+        }
+        ApiError::new(status, headers, "").into()
+    }
+
+    #[test]
+    fn test_backoff_grows_with_jitter() {
+        let policy = RetryPolicy::new(10, Duration::from_secs(600));
+        let error = api_error(StatusCode::SERVICE_UNAVAILABLE, None);
+
+        for retry in 0..8 {
+            let delay = policy.delay(retry, &error, Duration::ZERO).unwrap();
+            let full = backoff(retry);
+            assert!(delay >= full / 2 && delay <= full, "{delay:?} vs {full:?}");
+        }
+        assert_eq!(backoff(2), Duration::from_secs(2));
+        assert_eq!(backoff(30), MAX_DELAY);
+    }
+
+    #[test]
+    fn test_gives_up() {
+        let policy = RetryPolicy::new(3, Duration::from_secs(10));
+        let transient = api_error(StatusCode::TOO_MANY_REQUESTS, Some("2"));
+
+        assert_eq!(
+            policy.delay(0, &transient, Duration::ZERO),
+            Some(Duration::from_secs(2))
+        );
+        // Fatal errors fail fast
+        assert_eq!(
+            policy.delay(
+                0,
+                &api_error(StatusCode::UNAUTHORIZED, None),
+                Duration::ZERO
+            ),
+            None
+        );
+        // Out of retries
+        assert_eq!(policy.delay(3, &transient, Duration::ZERO), None);
+        // The wait would overrun the time budget
+        assert_eq!(policy.delay(1, &transient, Duration::from_secs(9)), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run_retries_transient_errors() {
+        let policy = RetryPolicy::new(5, Duration::from_secs(60));
+        let mut attempts = 0;
+
+        let (result, retries) = policy
+            .run(|| {
+                attempts += 1;
+                let attempt = attempts;
+                async move {
+                    match attempt {
+                        1 => Err(api_error(StatusCode::TOO_MANY_REQUESTS, Some("1"))),
+                        2 => Err(api_error(StatusCode::BAD_GATEWAY, None)),
+                        _ => Ok("done"),
+                    }
+                }
+            })
+            .await;
+
+        assert_eq!(result.unwrap(), "done");
+        assert_eq!(retries, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run_fails_fast_on_fatal_error() {
+        let policy = RetryPolicy::new(5, Duration::from_secs(60));
+        let mut attempts = 0;
+
+        let (result, retries) = policy
+            .run(|| {
+                attempts += 1;
+                async { Err::<(), _>(api_error(StatusCode::UNAUTHORIZED, None)) }
+            })
+            .await;
+
+        assert!(matches!(result, Err(TranscribeError::Api(_))));
+        assert_eq!((attempts, retries), (1, 0));
+    }
+}
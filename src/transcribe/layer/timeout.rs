@@ -0,0 +1,79 @@
+//! Time limit for transcriptions.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::transcribe::{
+    AudioStream, Bytes, Result, SegmentStream, TranscribeError, TranscribeOptions, Transcriber,
+    Transcript,
+};
+
+/// Fails transcriptions of the inner transcriber that take too long.
+///
+/// When the time runs out the inner transcription is cancelled through its
+/// [`TranscribeOptions::cancellation`], which also stops local models.
+pub struct Timeout<T> {
+    inner: T,
+    timeout: Duration,
+}
+
+impl<T> Timeout<T> {
+    /// Wrap `inner`, allowing each transcription `timeout`.
+    pub fn new(inner: T, timeout: Duration) -> Self {
+        Self { inner, timeout }
+    }
+}
+
+#[async_trait]
+impl<T: Transcriber> Transcriber for Timeout<T> {
+    async fn transcribe(&self, audio: Bytes, options: &TranscribeOptions) -> Result<Transcript> {
+        let cancellation = options.cancellation.child_token();
+        let options = options.clone().with_cancellation(cancellation.clone());
+
+        match tokio::time::timeout(self.timeout, self.inner.transcribe(audio, &options)).await {
+            Ok(result) => result,
+            Err(_) => {
+                cancellation.cancel();
+                Err(TranscribeError::TimedOut(self.timeout))
+            }
+        }
+    }
+
+    /// Streams last as long as the recording, so they have no time limit.
+    fn transcribe_stream<'a>(
+        &'a self,
+        audio: AudioStream,
+        options: &'a TranscribeOptions,
+    ) -> SegmentStream<'a> {
+        self.inner.transcribe_stream(audio, options)
+    }
+
+    async fn warm_up(&self) -> Result<()> {
+        self.inner.warm_up().await
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcribe::layer::tests::Scripted;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_times_out_and_cancels_inner() {
+        let inner = Scripted::ok("hello").with_delay(Duration::from_secs(10));
+        let cancelled = inner.cancelled.clone();
+        let timeout = Timeout::new(inner, Duration::from_secs(1));
+
+        let result = timeout
+            .transcribe(Bytes::new(), &TranscribeOptions::default())
+            .await;
+
+        assert!(matches!(result, Err(TranscribeError::TimedOut(_))));
+        assert!(cancelled.lock().unwrap().as_ref().unwrap().is_cancelled());
+    }
+}
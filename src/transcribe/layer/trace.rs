@@ -0,0 +1,94 @@
+//! Logging of transcription requests.
+
+use std::time::Instant;
+
+use async_trait::async_trait;
+use tracing::{Instrument, info, info_span, warn};
+
+use crate::transcribe::{
+    AudioStream, Bytes, Result, SegmentStream, TranscribeError, TranscribeOptions, Transcriber,
+    Transcript,
+};
+
+/// Logs each transcription of the inner transcriber in its own span, with
+/// its duration, throughput and result.
+pub struct Tracing<T> {
+    inner: T,
+}
+
+impl<T> Tracing<T> {
+    /// Wrap `inner`.
+    pub fn new(inner: T) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait]
+impl<T: Transcriber> Transcriber for Tracing<T> {
+    async fn transcribe(&self, audio: Bytes, options: &TranscribeOptions) -> Result<Transcript> {
+        let num_bytes = audio.len();
+        let span = info_span!(
+            "transcribe",
+            transcriber = self.inner.name(),
+            audio_bytes = num_bytes
+        );
+
+        async move {
+            let before = Instant::now();
+            let result = self.inner.transcribe(audio, options).await;
+            let duration = before.elapsed();
+
+            match &result {
+                Ok(transcript) => {
+                    let mb_per_second =
+                        num_bytes as f64 / (1024.0 * 1024.0) / duration.as_secs_f64();
+                    info!(
+                        duration = ?duration,
+                        mb_per_second = mb_per_second,
+                        backend = transcript.backend,
+                        model = transcript.model,
+                        language = transcript.language,
+                        segments = transcript.segments.len(),
+                        avg_logprob = transcript.avg_logprob(),
+                        "transcription completed"
+                    );
+                }
+                Err(TranscribeError::Cancelled) => {
+                    info!(duration = ?duration, "transcription cancelled");
+                }
+                Err(e) => {
+                    warn!(duration = ?duration, "transcription failed: {}", e);
+                }
+            }
+            result
+        }
+        .instrument(span)
+        .await
+    }
+
+    fn transcribe_stream<'a>(
+        &'a self,
+        audio: AudioStream,
+        options: &'a TranscribeOptions,
+    ) -> SegmentStream<'a> {
+        info!(
+            transcriber = self.inner.name(),
+            "streaming transcription started"
+        );
+        self.inner.transcribe_stream(audio, options)
+    }
+
+    async fn warm_up(&self) -> Result<()> {
+        let before = Instant::now();
+        let result = self.inner.warm_up().await;
+        match &result {
+            Ok(()) => info!(duration = ?before.elapsed(), "transcriber warmed up"),
+            Err(e) => warn!("warming up transcriber failed: {}", e),
+        }
+        result
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+}
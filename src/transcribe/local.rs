@@ -3,20 +3,44 @@
 //! This module provides local transcription using the whisper.cpp library
 //! via whisper-rs bindings.
 
+use std::collections::VecDeque;
+use std::ffi::c_void;
 use std::path::PathBuf;
-use std::sync::Mutex;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex, mpsc};
+use std::time::Duration;
 
 use async_trait::async_trait;
 use bytes::Bytes;
+use futures_util::stream::{self, StreamExt};
+use tokio::sync::Semaphore;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, info};
 use whisper_rs::{
-    FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters, WhisperState,
+    FullParams, SegmentCallbackData, WhisperContext, WhisperContextParameters, WhisperSegment,
+    WhisperState, get_lang_id, get_lang_str,
 };
 
-use super::model::{WhisperModel, model_path};
+use super::decoding::DecodingParams;
+use super::model::{ModelHeader, WhisperModel, model_path};
 #[cfg(target_os = "macos")]
 use super::model::{coreml_encoder_exists, coreml_encoder_path, ensure_coreml_encoder};
-use super::{Result, TranscribeError, Transcriber};
+use super::{
+    AudioStream, Result, Segment, SegmentStream, StreamSegment, TranscribeError, TranscribeOptions,
+    Transcriber, Transcript, Word,
+};
+use crate::audio::resample;
+use crate::core::{ModelEvent, Task};
+
+/// Sample rate whisper.cpp expects.
+const WHISPER_SAMPLE_RATE: u32 = 16000;
+
+/// Audio whisper.cpp looks at to detect the language, 30 seconds.
+const LANG_DETECT_SAMPLES: usize = 30 * WHISPER_SAMPLE_RATE as usize;
+
+/// How much new audio triggers another pass while streaming.
+const STREAM_STEP: Duration = Duration::from_secs(2);
 
 /// Configuration for the local Whisper transcriber.
 #[derive(Debug, Clone)]
@@ -28,6 +52,19 @@ pub struct LocalWhisperConfig {
     /// Enable CoreML acceleration on macOS.
     /// When enabled, downloads the CoreML encoder for ~3x faster encoding via Apple Neural Engine.
     pub coreml: bool,
+    /// Beam search, thresholds and other decoder settings.
+    pub decoding: DecodingParams,
+    /// Unload the model after it has been unused this long, to free memory.
+    pub unload_after_idle: Option<Duration>,
+    /// Number of recordings transcribed at once. Each needs its own
+    /// decoding buffers, but they share the model.
+    pub pool_size: u32,
+    /// Marker starting each speaker turn with tinydiarize models, see
+    /// [`Transcript::with_speaker_markers`].
+    pub speaker_marker: String,
+    /// Where to report the model loading and unloading, e.g. to show it in
+    /// the UI.
+    pub model_events: Option<Sender<ModelEvent>>,
 }
 
 impl LocalWhisperConfig {
@@ -37,10 +74,35 @@ impl LocalWhisperConfig {
             model,
             model_path: None,
             coreml: cfg!(target_os = "macos"), // Default to true on macOS
+            decoding: DecodingParams::default(),
+            unload_after_idle: None,
+            pool_size: 1,
+            speaker_marker: "— ".to_string(),
+            model_events: None,
         }
     }
 
+    /// Name of the model, the file name for a custom model file.
+    pub fn model_name(&self) -> String {
+        match &self.model_path {
+            Some(path) => path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_else(|| self.model.name().to_string()),
+            None => self.model.name().to_string(),
+        }
+    }
+
+    /// Whether the model is a tinydiarize model, which detects speaker
+    /// turns.
+    pub fn diarize(&self) -> bool {
+        self.model_name().contains("tdrz")
+    }
+
     /// Create a config with a custom model path.
+    ///
+    /// The file must be a GGML Whisper model; `model` is then only used
+    /// as a fallback name.
     pub fn with_model_path(mut self, path: PathBuf) -> Self {
         self.model_path = Some(path);
         self
@@ -51,40 +113,234 @@ impl LocalWhisperConfig {
         self.coreml = enabled;
         self
     }
+
+    /// Set the decoder settings.
+    pub fn with_decoding(mut self, decoding: DecodingParams) -> Self {
+        self.decoding = decoding;
+        self
+    }
+
+    /// Set the number of recordings transcribed at once.
+    pub fn with_pool_size(mut self, size: u32) -> Self {
+        self.pool_size = size;
+        self
+    }
+
+    /// Set the marker starting each speaker turn.
+    pub fn with_speaker_marker(mut self, marker: impl Into<String>) -> Self {
+        self.speaker_marker = marker.into();
+        self
+    }
+
+    /// Report the model loading and unloading to `sender`.
+    pub fn with_model_events(mut self, sender: Sender<ModelEvent>) -> Self {
+        self.model_events = Some(sender);
+        self
+    }
+
+    /// Unload the model after it has been unused for `idle`.
+    pub fn with_unload_after_idle(mut self, idle: Option<Duration>) -> Self {
+        self.unload_after_idle = idle;
+        self
+    }
 }
 
-/// Holds the WhisperContext and a reusable WhisperState.
-struct WhisperInstance {
-    /// Kept alive to ensure state remains valid.
-    _context: WhisperContext,
-    state: WhisperState,
+/// A loaded model and a pool of states to run it with.
+///
+/// All states share the model weights; each holds the buffers for one
+/// inference, so the pool size is the number of recordings transcribed at
+/// once.
+struct StatePool {
+    model: WhisperModel,
+    model_path: Option<PathBuf>,
+    unload_after_idle: Option<Duration>,
+    size: u32,
+    /// Lazily loaded model, `None` until first use and after unloading
+    context: Mutex<Option<Arc<WhisperContext>>>,
+    /// States not in use
+    states: Mutex<Vec<WhisperState>>,
+    /// One permit per state
+    permits: Arc<Semaphore>,
+    /// When a state was last used, to unload the model when idle
+    last_used: Mutex<Instant>,
+    events: Option<Sender<ModelEvent>>,
 }
 
-impl WhisperInstance {
-    fn new(context: WhisperContext) -> Result<Self> {
-        let state = context.create_state().map_err(|e| {
-            TranscribeError::TranscriptionFailed(format!("Failed to create state: {}", e))
-        })?;
-        Ok(Self {
-            _context: context,
-            state,
+impl StatePool {
+    fn new(config: &LocalWhisperConfig) -> Self {
+        let size = config.pool_size.max(1);
+        Self {
+            model: config.model,
+            model_path: config.model_path.clone(),
+            unload_after_idle: config.unload_after_idle,
+            size,
+            context: Mutex::new(None),
+            states: Mutex::new(Vec::new()),
+            permits: Arc::new(Semaphore::new(size as usize)),
+            last_used: Mutex::new(Instant::now()),
+            events: config.model_events.clone(),
+        }
+    }
+
+    /// Run `f` with a state on the blocking thread pool, once one is free.
+    ///
+    /// Loads the model first if needed. Waiting for a state stops when
+    /// `cancellation` is cancelled.
+    async fn run<T, F>(self: &Arc<Self>, cancellation: &CancellationToken, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&WhisperContext, &mut WhisperState) -> Result<T> + Send + 'static,
+    {
+        let permit = cancellation
+            .run_until_cancelled(self.permits.clone().acquire_owned())
+            .await
+            .ok_or(TranscribeError::Cancelled)?
+            .expect("state pool semaphore is never closed");
+
+        let pool = self.clone();
+        tokio::task::spawn_blocking(move || {
+            // Held until the state is back in the pool
+            let _permit = permit;
+            pool.touch();
+            let context = pool.context()?;
+            let mut state = match pool.states.lock().unwrap().pop() {
+                Some(state) => state,
+                None => context.create_state().map_err(|e| {
+                    TranscribeError::TranscriptionFailed(format!("Failed to create state: {}", e))
+                })?,
+            };
+
+            let result = f(&context, &mut state);
+            pool.states.lock().unwrap().push(state);
+            pool.touch();
+            result
         })
+        .await
+        .map_err(|e| {
+            TranscribeError::TranscriptionFailed(format!("Inference task failed: {}", e))
+        })?
+    }
+
+    /// Get the model, loading it if needed.
+    ///
+    /// Loading is reported as [`ModelEvent::Loading`] and
+    /// [`ModelEvent::Loaded`], on first use and after an idle unload alike.
+    fn context(self: &Arc<Self>) -> Result<Arc<WhisperContext>> {
+        let mut context = self.context.lock().unwrap();
+        if let Some(context) = context.as_ref() {
+            return Ok(context.clone());
+        }
+
+        self.notify(ModelEvent::Loading);
+        let loaded = self.load();
+        self.notify(ModelEvent::Loaded);
+
+        let loaded = Arc::new(loaded?);
+        *context = Some(loaded.clone());
+        self.schedule_unload();
+        Ok(loaded)
+    }
+
+    /// Load the model from disk.
+    fn load(&self) -> Result<WhisperContext> {
+        let path = match &self.model_path {
+            Some(p) => p.clone(),
+            None => model_path(self.model)
+                .map_err(|e| TranscribeError::TranscriptionFailed(e.to_string()))?,
+        };
+
+        let header = ModelHeader::read(&path)
+            .map_err(|e| TranscribeError::TranscriptionFailed(format!("{:#}", e)))?;
+        info!(path = ?path, header = ?header, states = self.size, "Loading Whisper model");
+
+        let loaded = WhisperContext::new_with_params(
+            path.to_str().ok_or_else(|| {
+                TranscribeError::TranscriptionFailed("Invalid model path".to_string())
+            })?,
+            WhisperContextParameters::default(),
+        )
+        .map_err(|e| {
+            TranscribeError::TranscriptionFailed(format!("Failed to load model: {}", e))
+        })?;
+
+        info!("Whisper model loaded successfully");
+        Ok(loaded)
+    }
+
+    /// Report a model event, if anyone listens.
+    fn notify(&self, event: ModelEvent) {
+        if let Some(events) = &self.events {
+            events.send(event).ok();
+        }
+    }
+
+    /// Mark the model as in use.
+    fn touch(&self) {
+        *self.last_used.lock().unwrap() = Instant::now();
+    }
+
+    /// Unload the model once it has been idle for
+    /// [`LocalWhisperConfig::unload_after_idle`].
+    ///
+    /// The next transcription loads it again.
+    fn schedule_unload(self: &Arc<Self>) {
+        let Some(idle) = self.unload_after_idle else {
+            return;
+        };
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            debug!("No async runtime, keeping the Whisper model loaded");
+            return;
+        };
+
+        let pool = Arc::downgrade(self);
+        runtime.spawn(async move {
+            loop {
+                // Stop when the client is gone
+                let Some(deadline) = pool.upgrade().map(|p| *p.last_used.lock().unwrap() + idle)
+                else {
+                    return;
+                };
+                tokio::time::sleep_until(deadline).await;
+
+                let Some(pool) = pool.upgrade() else {
+                    return;
+                };
+                if pool.last_used.lock().unwrap().elapsed() < idle {
+                    continue;
+                }
+                // Still transcribing, check again once it could be idle
+                let Ok(_permits) = pool.permits.try_acquire_many(pool.size) else {
+                    tokio::time::sleep(idle).await;
+                    continue;
+                };
+                pool.states.lock().unwrap().clear();
+                if pool.context.lock().unwrap().take().is_some() {
+                    info!(idle = ?idle, "Unloaded idle Whisper model");
+                    pool.notify(ModelEvent::Unloaded);
+                }
+                return;
+            }
+        });
     }
 }
 
 /// Local Whisper transcriber using whisper.cpp.
+///
+/// Inference runs on tokio's blocking thread pool, so it doesn't hold up
+/// other tasks on the runtime.
 pub struct LocalWhisperClient {
     config: LocalWhisperConfig,
-    /// Lazily initialized whisper instance (context + reusable state).
-    instance: Mutex<Option<WhisperInstance>>,
+    pool: Arc<StatePool>,
 }
 
 impl LocalWhisperClient {
     /// Create a new local Whisper client.
+    ///
+    /// The model is loaded on first use, or by [`Transcriber::warm_up`].
     pub fn new(config: LocalWhisperConfig) -> Self {
         Self {
+            pool: Arc::new(StatePool::new(&config)),
             config,
-            instance: Mutex::new(None),
         }
     }
 
@@ -93,9 +349,12 @@ impl LocalWhisperClient {
     /// This downloads the CoreML encoder if it doesn't exist and CoreML is enabled.
     /// Must be called before loading the model since whisper.cpp looks for the
     /// encoder at model load time.
+    ///
+    /// Custom model files are skipped, whisper.cpp uses an encoder next to
+    /// the file if there is one.
     #[cfg(target_os = "macos")]
     async fn ensure_coreml_setup(&self) -> Result<()> {
-        if !self.config.coreml {
+        if !self.config.coreml || self.config.model_path.is_some() {
             return Ok(());
         }
 
@@ -140,39 +399,8 @@ impl LocalWhisperClient {
         Ok(())
     }
 
-    /// Get or initialize the whisper instance, returning a guard.
-    fn ensure_instance(&self) -> Result<std::sync::MutexGuard<'_, Option<WhisperInstance>>> {
-        let mut guard = self.instance.lock().map_err(|e| {
-            TranscribeError::TranscriptionFailed(format!("Failed to lock instance: {}", e))
-        })?;
-        if guard.is_none() {
-            let path = match &self.config.model_path {
-                Some(p) => p.clone(),
-                None => model_path(self.config.model)
-                    .map_err(|e| TranscribeError::TranscriptionFailed(e.to_string()))?,
-            };
-
-            info!(path = ?path, "Loading Whisper model");
-
-            let ctx = WhisperContext::new_with_params(
-                path.to_str().ok_or_else(|| {
-                    TranscribeError::TranscriptionFailed("Invalid model path".to_string())
-                })?,
-                WhisperContextParameters::default(),
-            )
-            .map_err(|e| {
-                TranscribeError::TranscriptionFailed(format!("Failed to load model: {}", e))
-            })?;
-
-            let instance = WhisperInstance::new(ctx)?;
-            info!("Whisper model loaded successfully");
-            *guard = Some(instance);
-        }
-        Ok(guard)
-    }
-
     /// Convert WAV audio data to 16kHz mono f32 samples.
-    fn convert_audio(&self, audio: &[u8]) -> Result<Vec<f32>> {
+    fn convert_audio(audio: &[u8]) -> Result<Vec<f32>> {
         use std::io::Cursor;
 
         let cursor = Cursor::new(audio);
@@ -230,9 +458,8 @@ impl LocalWhisperClient {
         };
 
         // Resample to 16kHz if needed
-        let target_rate = 16000;
-        let resampled = if sample_rate != target_rate {
-            resample(&mono_samples, sample_rate, target_rate)
+        let resampled = if sample_rate != WHISPER_SAMPLE_RATE {
+            resample(&mono_samples, sample_rate, WHISPER_SAMPLE_RATE)
         } else {
             mono_samples
         };
@@ -247,89 +474,403 @@ impl LocalWhisperClient {
     }
 }
 
-/// Linear interpolation resampling.
+/// Configure transcription parameters.
+fn full_params<'a>(
+    decoding: &DecodingParams,
+    options: &'a TranscribeOptions,
+) -> FullParams<'a, 'a> {
+    let mut params = FullParams::new(decoding.strategy());
+    decoding.apply(&mut params);
+
+    // Set language if provided, otherwise auto-detect
+    params.set_language(options.language());
+    if let Some(prompt) = options.prompt() {
+        params.set_initial_prompt(prompt);
+    }
+    if let Some(temperature) = options.temperature {
+        params.set_temperature(temperature);
+    }
+    params.set_translate(options.task == Task::Translate);
+
+    // SAFETY: the callback only reads the token, and the params borrow
+    // `options`, so the token outlives every call
+    unsafe {
+        params.set_abort_callback(Some(abort_if_cancelled));
+        params.set_abort_callback_user_data(
+            &options.cancellation as *const CancellationToken as *mut c_void,
+        );
+    }
+
+    // Disable printing to stdout
+    params.set_print_special(false);
+    params.set_print_progress(false);
+    params.set_print_realtime(false);
+    params.set_print_timestamps(false);
+
+    params
+}
+
+/// Abort callback for whisper.cpp, `user_data` points to a
+/// [`CancellationToken`].
+unsafe extern "C" fn abort_if_cancelled(user_data: *mut c_void) -> bool {
+    // SAFETY: set up by `full_params`
+    let token = unsafe { &*(user_data as *const CancellationToken) };
+    token.is_cancelled()
+}
+
+/// Set the language of `options` to the most likely of the allowed
+/// languages, if auto-detection is restricted to a set.
+fn pin_language(
+    state: &mut WhisperState,
+    decoding: &DecodingParams,
+    samples: &[f32],
+    options: TranscribeOptions,
+) -> Result<TranscribeOptions> {
+    if options.language.is_some() || options.languages.is_empty() || samples.is_empty() {
+        return Ok(options);
+    }
+
+    let threads = decoding.threads();
+    let window = &samples[..samples.len().min(LANG_DETECT_SAMPLES)];
+    let (_, probs) = state
+        .pcm_to_mel(window, threads)
+        .and_then(|()| state.lang_detect(0, threads))
+        .map_err(|e| {
+            TranscribeError::TranscriptionFailed(format!("Language detection failed: {}", e))
+        })?;
+    let candidates = options.languages.iter().filter_map(|code| {
+        let id = get_lang_id(code)?;
+        Some((code.as_str(), *probs.get(id as usize)?))
+    });
+    let Some(language) = most_likely(candidates).map(str::to_string) else {
+        return Ok(options);
+    };
+    debug!(language, allowed = ?options.languages, "Detected language");
+    Ok(options.with_language(language))
+}
+
+/// The language with the highest probability.
+fn most_likely<'a>(candidates: impl IntoIterator<Item = (&'a str, f32)>) -> Option<&'a str> {
+    candidates
+        .into_iter()
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(language, _)| language)
+}
+
+/// Run whisper on 16kHz mono samples.
 ///
-/// Technically this can introduce aliasing artifacts when downsampling without
-/// a low-pass filter, but in practice the output sounds identical for speech.
-/// Consider using the `rubato` crate for sinc interpolation if quality issues arise.
-fn resample(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
-    if from_rate == to_rate {
-        return samples.to_vec();
-    }
-
-    let ratio = from_rate as f64 / to_rate as f64;
-    let new_len = (samples.len() as f64 / ratio) as usize;
-    let mut result = Vec::with_capacity(new_len);
-
-    for i in 0..new_len {
-        let src_idx = i as f64 * ratio;
-        let src_idx_floor = src_idx.floor() as usize;
-        let frac = src_idx - src_idx_floor as f64;
-
-        let sample = if src_idx_floor + 1 < samples.len() {
-            let s0 = samples[src_idx_floor] as f64;
-            let s1 = samples[src_idx_floor + 1] as f64;
-            (s0 * (1.0 - frac) + s1 * frac) as f32
-        } else if src_idx_floor < samples.len() {
-            samples[src_idx_floor]
+/// A run aborted through the cancellation token fails with
+/// [`TranscribeError::Cancelled`].
+fn run_full(
+    state: &mut WhisperState,
+    params: FullParams<'_, '_>,
+    samples: &[f32],
+    cancellation: &CancellationToken,
+) -> Result<()> {
+    if cancellation.is_cancelled() {
+        return Err(TranscribeError::Cancelled);
+    }
+    let result = state.full(params, samples);
+    if cancellation.is_cancelled() {
+        return Err(TranscribeError::Cancelled);
+    }
+    result.map_err(|e| {
+        TranscribeError::TranscriptionFailed(format!("Transcription failed: {}", e))
+    })?;
+    Ok(())
+}
+
+/// Progress of a streaming transcription.
+struct LiveTranscription {
+    /// Remaining audio, `None` once the stream has ended or failed
+    audio: Option<AudioStream>,
+    /// Mono samples at the stream's sample rate
+    samples: Vec<f32>,
+    sample_rate: u32,
+    /// Samples covered by final segments
+    committed: usize,
+    /// Samples already covered by the last pass
+    decoded: usize,
+    /// Segments ready to be yielded
+    queue: VecDeque<Result<StreamSegment>>,
+    /// Language detected on the first pass, kept for the following ones
+    language: Option<String>,
+}
+
+impl LiveTranscription {
+    fn offset(&self, samples: usize) -> Duration {
+        Duration::from_secs_f64(samples as f64 / self.sample_rate.max(1) as f64)
+    }
+}
+
+impl LocalWhisperClient {
+    /// Decode the uncommitted audio and queue the resulting segments.
+    ///
+    /// On the last pass every segment is final. Otherwise all but the last
+    /// segment are considered settled: they are committed as final and the
+    /// next pass starts after them, while the last one stays partial.
+    async fn stream_pass(
+        &self,
+        live: &mut LiveTranscription,
+        options: &TranscribeOptions,
+        last: bool,
+    ) -> Result<()> {
+        self.ensure_coreml_setup().await?;
+
+        let window = resample(
+            &live.samples[live.committed..],
+            live.sample_rate,
+            WHISPER_SAMPLE_RATE,
+        );
+        live.decoded = live.samples.len();
+        let options = match &live.language {
+            Some(language) => options.clone().with_language(language),
+            None => options.clone(),
+        };
+        let (segments, language) = self.decode_segments(window, &options).await?;
+        live.language = language;
+
+        let start = live.offset(live.committed);
+        let settled = if last {
+            segments.len()
         } else {
-            0.0
+            segments.len().saturating_sub(1)
         };
+        let mut committed_until = live.committed;
+        for (i, segment) in segments.into_iter().enumerate() {
+            let end = centis(segment.end_timestamp);
+            let segment = Segment::new(
+                segment.text,
+                start + centis(segment.start_timestamp),
+                start + end,
+            );
+            if i < settled {
+                live.queue
+                    .push_back(Ok(StreamSegment::final_segment(segment)));
+                committed_until =
+                    live.committed + (end.as_secs_f64() * live.sample_rate as f64) as usize;
+            } else {
+                live.queue.push_back(Ok(StreamSegment::partial(segment)));
+            }
+        }
+        live.committed = if last {
+            live.samples.len()
+        } else {
+            committed_until.min(live.samples.len())
+        };
+        Ok(())
+    }
 
-        result.push(sample);
+    /// Run whisper on 16kHz mono samples, collecting segments as they are
+    /// decoded.
+    ///
+    /// Also returns the language the audio was decoded in, if it was set or
+    /// picked from the allowed languages.
+    async fn decode_segments(
+        &self,
+        samples: Vec<f32>,
+        options: &TranscribeOptions,
+    ) -> Result<(Vec<SegmentCallbackData>, Option<String>)> {
+        let decoding = self.config.decoding.clone();
+        let owned_options = options.clone();
+        self.pool
+            .run(&options.cancellation, move |_, state| {
+                let options = pin_language(state, &decoding, &samples, owned_options)?;
+                let (sender, receiver) = mpsc::channel();
+                let mut params = full_params(&decoding, &options);
+                params.set_segment_callback_safe(move |segment: SegmentCallbackData| {
+                    sender.send(segment).ok();
+                });
+
+                run_full(state, params, &samples, &options.cancellation)?;
+                Ok((receiver.try_iter().collect(), options.language.clone()))
+            })
+            .await
     }
+}
 
-    result
+/// Convert a whisper.cpp timestamp, in centiseconds.
+fn centis(timestamp: i64) -> Duration {
+    Duration::from_millis(timestamp.max(0) as u64 * 10)
+}
+
+/// Convert a decoded segment, including word timings and confidence.
+///
+/// Words are assembled from tokens: a token starting with a space begins a
+/// new word. Special tokens (timestamps, end of text) are skipped.
+fn convert_segment(segment: &WhisperSegment<'_>, eot: i32) -> Segment {
+    let mut words: Vec<Word> = Vec::new();
+    let mut logprob_sum = 0.0;
+    let mut tokens = 0;
+
+    for i in 0..segment.n_tokens() {
+        let Some(token) = segment.get_token(i) else {
+            continue;
+        };
+        if token.token_id() >= eot {
+            continue;
+        }
+        let data = token.token_data();
+        logprob_sum += data.plog;
+        tokens += 1;
+
+        let Ok(text) = token.to_str_lossy() else {
+            continue;
+        };
+        match words.last_mut() {
+            Some(word) if !text.starts_with(' ') => {
+                word.text.push_str(&text);
+                word.end = centis(data.t1);
+                word.probability = word.probability.map(|p| p.min(data.p));
+            }
+            _ => words.push(Word {
+                text: text.trim_start().to_string(),
+                start: centis(data.t0),
+                end: centis(data.t1),
+                probability: Some(data.p),
+            }),
+        }
+    }
+    words.retain(|w| !w.text.is_empty());
+
+    Segment {
+        text: segment
+            .to_str_lossy()
+            .map(|s| s.into_owned())
+            .unwrap_or_default(),
+        start: centis(segment.start_timestamp()),
+        end: centis(segment.end_timestamp()),
+        words,
+        avg_logprob: (tokens > 0).then(|| logprob_sum / tokens as f32),
+        no_speech_prob: Some(segment.no_speech_probability()),
+        speaker_turn: segment.next_segment_speaker_turn(),
+    }
 }
 
 #[async_trait]
 impl Transcriber for LocalWhisperClient {
-    async fn transcribe(&self, audio: Bytes, language: Option<&str>) -> Result<String> {
+    async fn transcribe(&self, audio: Bytes, options: &TranscribeOptions) -> Result<Transcript> {
         // Ensure CoreML encoder is available (macOS only, downloads if needed)
         self.ensure_coreml_setup().await?;
 
-        // Convert audio to the format whisper expects
-        let samples = self.convert_audio(&audio)?;
-
-        // Get the instance (ensures model is loaded)
-        let mut guard = self.ensure_instance()?;
-        let instance = guard.as_mut().expect("instance should be initialized");
-
-        // Configure transcription parameters
-        let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+        let decoding = self.config.decoding.clone();
+        let diarize = self.config.diarize();
+        let owned_options = options.clone();
+        let (segments, language) = self
+            .pool
+            .run(&options.cancellation, move |context, state| {
+                // Convert audio to the format whisper expects
+                let samples = Self::convert_audio(&audio)?;
+                let options = pin_language(state, &decoding, &samples, owned_options)?;
+
+                let mut params = full_params(&decoding, &options);
+                params.set_token_timestamps(true);
+                params.set_tdrz_enable(diarize);
+
+                // Run transcription
+                run_full(state, params, &samples, &options.cancellation)?;
+
+                // Collect all segments into the result
+                let eot = context.token_eot();
+                let segments: Vec<Segment> = state
+                    .as_iter()
+                    .map(|segment| convert_segment(&segment, eot))
+                    .collect();
+                Ok((segments, get_lang_str(state.full_lang_id_from_state())))
+            })
+            .await?;
+        let text: String = segments.iter().map(|s| s.text.as_str()).collect();
 
-        // Set language if provided
+        let mut transcript = Transcript::new(text.trim(), self.name())
+            .with_segments(segments)
+            .with_model(self.config.model_name());
         if let Some(lang) = language {
-            params.set_language(Some(lang));
-        } else {
-            // Auto-detect language
-            params.set_language(None);
+            transcript = transcript.with_language(lang);
+        }
+        if diarize {
+            transcript = transcript.with_speaker_markers(&self.config.speaker_marker);
         }
+        Ok(transcript)
+    }
 
-        // Disable printing to stdout
-        params.set_print_special(false);
-        params.set_print_progress(false);
-        params.set_print_realtime(false);
-        params.set_print_timestamps(false);
+    /// Load the model and run it once on a second of silence, so the first
+    /// transcription doesn't wait for the model to load or the GPU to set
+    /// up.
+    async fn warm_up(&self) -> Result<()> {
+        self.ensure_coreml_setup().await?;
 
-        // Run transcription
-        instance.state.full(params, &samples).map_err(|e| {
-            TranscribeError::TranscriptionFailed(format!("Transcription failed: {}", e))
-        })?;
+        let decoding = self.config.decoding.clone();
+        let cancellation = CancellationToken::new();
+        self.pool
+            .run(&cancellation, move |_, state| {
+                let options = TranscribeOptions::default();
+                let params = full_params(&decoding, &options);
+                let silence = vec![0.0; WHISPER_SAMPLE_RATE as usize];
+                run_full(state, params, &silence, &options.cancellation)
+            })
+            .await
+    }
 
-        // Collect all segments into the result
-        let num_segments = instance.state.full_n_segments();
+    /// Transcribe audio while it is being recorded.
+    ///
+    /// Whenever enough new audio has arrived, the audio that isn't covered
+    /// by final segments yet is decoded again, so partial text keeps up with
+    /// the speaker without re-decoding the whole recording every time.
+    fn transcribe_stream<'a>(
+        &'a self,
+        audio: AudioStream,
+        options: &'a TranscribeOptions,
+    ) -> SegmentStream<'a> {
+        let live = LiveTranscription {
+            audio: Some(audio),
+            samples: Vec::new(),
+            sample_rate: 0,
+            committed: 0,
+            decoded: 0,
+            queue: VecDeque::new(),
+            language: None,
+        };
 
-        let mut result = String::new();
-        for i in 0..num_segments {
-            if let Some(segment) = instance.state.get_segment(i)
-                && let Ok(text) = segment.to_str_lossy()
-            {
-                result.push_str(&text);
-            }
-        }
+        stream::unfold(live, move |mut live| async move {
+            loop {
+                if let Some(segment) = live.queue.pop_front() {
+                    return Some((segment, live));
+                }
 
-        Ok(result.trim().to_string())
+                let chunk = live.audio.as_mut()?.next().await;
+                let result = match chunk {
+                    Some(chunk)
+                        if live.sample_rate != 0 && chunk.sample_rate != live.sample_rate =>
+                    {
+                        Err(TranscribeError::InvalidAudioFormat(format!(
+                            "Audio stream changed sample rate from {} Hz to {} Hz",
+                            live.sample_rate, chunk.sample_rate
+                        )))
+                    }
+                    Some(chunk) => {
+                        live.sample_rate = chunk.sample_rate;
+                        live.samples.extend(chunk.to_mono());
+                        if live.offset(live.samples.len() - live.decoded) < STREAM_STEP {
+                            continue;
+                        }
+                        self.stream_pass(&mut live, options, false).await
+                    }
+                    None => {
+                        live.audio = None;
+                        if live.committed == live.samples.len() {
+                            continue;
+                        }
+                        self.stream_pass(&mut live, options, true).await
+                    }
+                };
+
+                if let Err(e) = result {
+                    live.audio = None;
+                    live.queue.push_back(Err(e));
+                }
+            }
+        })
+        .boxed()
     }
 
     fn name(&self) -> &str {
@@ -342,11 +883,12 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_resample() {
-        // Simple test: downsampling should produce fewer samples
-        let samples: Vec<f32> = (0..48000).map(|i| (i as f32 / 48000.0).sin()).collect();
-        let resampled = resample(&samples, 48000, 16000);
-        assert_eq!(resampled.len(), 16000);
+    fn test_most_likely() {
+        assert_eq!(
+            most_likely([("en", 0.2), ("de", 0.7), ("fr", 0.1)]),
+            Some("de")
+        );
+        assert_eq!(most_likely([]), None);
     }
 
     #[test]
@@ -354,6 +896,39 @@ mod tests {
         let config = LocalWhisperConfig::new(WhisperModel::BaseQ8_0);
         assert_eq!(config.model, WhisperModel::BaseQ8_0);
         assert!(config.model_path.is_none());
+        assert!(config.unload_after_idle.is_none());
+    }
+
+    #[test]
+    fn test_diarize() {
+        assert!(!LocalWhisperConfig::new(WhisperModel::BaseQ8_0).diarize());
+        assert!(LocalWhisperConfig::new(WhisperModel::SmallEnTdrz).diarize());
+    }
+
+    #[test]
+    fn test_model_name() {
+        let config = LocalWhisperConfig::new(WhisperModel::BaseQ8_0);
+        assert_eq!(config.model_name(), "base-q8_0");
+
+        let custom = config.with_model_path(PathBuf::from("/models/ggml-medical-v2.bin"));
+        assert_eq!(custom.model_name(), "ggml-medical-v2");
+    }
+
+    #[tokio::test]
+    async fn test_pool_wait_is_cancellable() {
+        let pool = Arc::new(StatePool::new(
+            &LocalWhisperConfig::new(WhisperModel::TinyQ8_0).with_pool_size(0),
+        ));
+        assert_eq!(pool.size, 1);
+
+        // Take the only state, so the next request has to wait
+        let _busy = pool.permits.clone().acquire_owned().await.unwrap();
+        let cancellation = CancellationToken::new();
+        let waiting = pool.run(&cancellation, |_, _| Ok(()));
+        cancellation.cancel();
+
+        assert!(matches!(waiting.await, Err(TranscribeError::Cancelled)));
+        assert!(pool.context.lock().unwrap().is_none());
     }
 
     /// Integration test for local whisper transcription.
@@ -490,15 +1065,19 @@ mod tests {
         let config = LocalWhisperConfig::new(model);
         let client = LocalWhisperClient::new(config);
 
-        let result = rt.block_on(async { client.transcribe(audio_data.into(), None).await });
+        let result = rt.block_on(async {
+            client
+                .transcribe(audio_data.into(), &TranscribeOptions::default())
+                .await
+        });
 
         match result {
-            Ok(text) => {
+            Ok(transcript) => {
                 eprintln!("Transcription successful!");
                 eprintln!("---");
-                eprintln!("{}", text);
+                eprintln!("{}", transcript.text);
                 eprintln!("---");
-                assert!(!text.is_empty(), "Transcription should not be empty");
+                assert!(!transcript.is_empty(), "Transcription should not be empty");
             }
             Err(e) => {
                 panic!("Transcription failed: {:?}", e);
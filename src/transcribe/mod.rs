@@ -3,29 +3,58 @@
 //! This crate provides a trait-based abstraction for audio transcription,
 //! with implementations for OpenAI's Whisper API and local Whisper models.
 
+mod error;
+mod fallback;
+mod filter;
+pub mod language;
+pub mod layer;
 mod openai;
+mod options;
+mod stream;
+mod transcript;
 
+#[cfg(feature = "local-whisper")]
+mod decoding;
 #[cfg(feature = "local-whisper")]
 mod local;
 #[cfg(feature = "local-whisper")]
 mod model;
 
+use std::sync::Arc;
+use std::time::Duration;
+
 use async_trait::async_trait;
 pub use bytes::Bytes;
 #[cfg(feature = "local-whisper")]
+pub use decoding::DecodingParams;
+pub use error::ApiError;
+pub use fallback::FallbackTranscriber;
+pub use filter::OutputFilter;
+pub use layer::{
+    Cache, Metrics, MetricsSnapshot, RateLimit, Retry, RetryPolicy, Timeout, Tracing,
+    TranscriberExt, TranscriberMetrics,
+};
+#[cfg(feature = "local-whisper")]
 pub use local::{LocalWhisperClient, LocalWhisperConfig};
 #[cfg(feature = "local-whisper")]
-pub use model::{WhisperModel, download_model, ensure_model, model_exists, model_path};
+pub use model::{
+    ModelHeader, WhisperModel, download_model, ensure_model, model_exists, model_path,
+    verify_model_file,
+};
 #[cfg(all(feature = "local-whisper", target_os = "macos"))]
 pub use model::{coreml_encoder_exists, coreml_encoder_path, ensure_coreml_encoder};
-pub use openai::{OpenAIClient, OpenAIConfig};
+pub use openai::{OpenAIClient, OpenAIConfig, ResponseFormat, TimestampGranularity};
+pub use options::TranscribeOptions;
+pub use stream::{AudioChunk, AudioStream, SegmentStream, StreamSegment, buffered};
 use thiserror::Error;
+pub use tokio_util::sync::CancellationToken;
+pub use transcript::{Segment, Transcript, Word};
 
 /// Errors that can occur during transcription.
 #[derive(Debug, Error)]
 pub enum TranscribeError {
     #[error("API request failed: {0}")]
-    ApiError(String),
+    Api(Box<ApiError>),
 
     #[error("No API key configured")]
     NoApiKey,
@@ -38,6 +67,45 @@ pub enum TranscribeError {
 
     #[error("Transcription failed: {0}")]
     TranscriptionFailed(String),
+
+    #[error("Transcription cancelled")]
+    Cancelled,
+
+    #[error("Transcription timed out after {0:?}")]
+    TimedOut(Duration),
+}
+
+impl From<ApiError> for TranscribeError {
+    fn from(error: ApiError) -> Self {
+        Self::Api(Box::new(error))
+    }
+}
+
+impl TranscribeError {
+    /// Whether the failure is transient, so the request may succeed if
+    /// sent again.
+    ///
+    /// Network problems, timeouts and rate limits are; a rejected key, bad audio or a
+    /// failed local transcription aren't.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Api(e) => e.is_retryable(),
+            Self::NetworkError(e) => !e.is_builder() && !e.is_decode() && !e.is_redirect(),
+            Self::TimedOut(_) => true,
+            Self::NoApiKey
+            | Self::InvalidAudioFormat(_)
+            | Self::TranscriptionFailed(_)
+            | Self::Cancelled => false,
+        }
+    }
+
+    /// How long the server asked us to wait before retrying, if it did.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::Api(e) => e.retry_after(),
+            _ => None,
+        }
+    }
 }
 
 /// Result type for transcription operations.
@@ -49,15 +117,58 @@ pub type Result<T> = std::result::Result<T, TranscribeError>;
 /// other cloud providers, etc.)
 #[async_trait]
 pub trait Transcriber: Send + Sync {
-    /// Transcribe audio to text.
+    /// Transcribe audio to a [`Transcript`].
     ///
     /// # Arguments
     /// * `audio` - Raw audio data (WAV, MP3, etc.) as reference-counted bytes.
     ///             Use `Bytes::from(vec)` to convert from Vec<u8> (zero-copy).
     ///             Cloning Bytes is O(1) which allows efficient retries.
-    /// * `language` - Optional language hint (ISO 639-1 code, e.g., "en")
-    async fn transcribe(&self, audio: Bytes, language: Option<&str>) -> Result<String>;
+    /// * `options` - Language hint, prompt and other per-request options
+    async fn transcribe(&self, audio: Bytes, options: &TranscribeOptions) -> Result<Transcript>;
+
+    /// Transcribe audio while it is being recorded.
+    ///
+    /// Yields partial segments as audio comes in and final segments once
+    /// they are settled, see [`StreamSegment`]. The default implementation
+    /// waits for the whole stream and yields the segments of
+    /// [`transcribe`](Self::transcribe) as final segments.
+    fn transcribe_stream<'a>(
+        &'a self,
+        audio: AudioStream,
+        options: &'a TranscribeOptions,
+    ) -> SegmentStream<'a> {
+        buffered(self, audio, options)
+    }
+
+    /// Prepare for the first transcription, e.g. by loading a local model,
+    /// so it doesn't pay the setup time. Does nothing by default.
+    async fn warm_up(&self) -> Result<()> {
+        Ok(())
+    }
 
     /// Returns the name of this transcriber for logging/debugging.
     fn name(&self) -> &str;
 }
+
+#[async_trait]
+impl<T: Transcriber + ?Sized> Transcriber for Arc<T> {
+    async fn transcribe(&self, audio: Bytes, options: &TranscribeOptions) -> Result<Transcript> {
+        (**self).transcribe(audio, options).await
+    }
+
+    fn transcribe_stream<'a>(
+        &'a self,
+        audio: AudioStream,
+        options: &'a TranscribeOptions,
+    ) -> SegmentStream<'a> {
+        (**self).transcribe_stream(audio, options)
+    }
+
+    async fn warm_up(&self) -> Result<()> {
+        (**self).warm_up().await
+    }
+
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+}
@@ -4,7 +4,7 @@
 
 use std::fs::{self, File};
 use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
 use tracing::{info, warn};
@@ -14,6 +14,12 @@ use crate::core::models_dir;
 /// Base URL for downloading Whisper models from Hugging Face.
 const MODEL_BASE_URL: &str = "https://huggingface.co/ggerganov/whisper.cpp/resolve/main";
 
+/// Magic number at the start of GGML model files.
+const GGML_MAGIC: u32 = 0x6767_6d6c;
+
+/// Audio context length of every Whisper model (30 seconds of frames).
+const WHISPER_AUDIO_CTX: i32 = 1500;
+
 macro_rules! define_models {
     (
         $(
@@ -389,8 +395,87 @@ pub fn model_exists(model: WhisperModel) -> Result<bool> {
     Ok(path.exists())
 }
 
+/// Hyperparameters from the header of a GGML Whisper model file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModelHeader {
+    pub n_vocab: i32,
+    pub n_audio_layer: i32,
+    pub n_text_layer: i32,
+    /// 80 for most models, 128 for large-v3 and its derivatives
+    pub n_mels: i32,
+    /// Weight type, e.g. 1 for f16, 8 for q5_0
+    pub ftype: i32,
+}
+
+impl ModelHeader {
+    /// Read and check the header of a model file.
+    ///
+    /// Fails with a clear error if the file isn't a GGML Whisper model,
+    /// rather than leaving it to whisper.cpp.
+    pub fn read(path: &Path) -> Result<Self> {
+        let mut file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+        let mut header = [0u8; 48];
+        file.read_exact(&mut header)
+            .with_context(|| format!("{:?} is too short to be a Whisper model", path))?;
+        Self::parse(&header).with_context(|| format!("Invalid model file {:?}", path))
+    }
+
+    fn parse(header: &[u8; 48]) -> Result<Self> {
+        let field = |i: usize| i32::from_le_bytes(header[i * 4..i * 4 + 4].try_into().unwrap());
+
+        if &header[..4] == b"GGUF" {
+            anyhow::bail!(
+                "GGUF files aren't supported by whisper.cpp, use a GGML model \
+                 (see whisper.cpp's models/convert-h5-to-ggml.py)"
+            );
+        }
+        if field(0) as u32 != GGML_MAGIC {
+            anyhow::bail!("not a GGML model file");
+        }
+
+        // Fields after the magic: n_vocab, n_audio_ctx, n_audio_state,
+        // n_audio_head, n_audio_layer, n_text_ctx, n_text_state,
+        // n_text_head, n_text_layer, n_mels, ftype
+        let header = Self {
+            n_vocab: field(1),
+            n_audio_layer: field(5),
+            n_text_layer: field(9),
+            n_mels: field(10),
+            ftype: field(11),
+        };
+        if field(2) != WHISPER_AUDIO_CTX
+            || !matches!(header.n_mels, 80 | 128)
+            || header.n_vocab < 51864
+            || header.n_audio_layer <= 0
+            || header.n_text_layer <= 0
+        {
+            anyhow::bail!("GGML file is not a Whisper model");
+        }
+        Ok(header)
+    }
+}
+
+/// Checks a user-supplied model file before it is loaded.
+///
+/// Verifies the header and, if `expected_sha1` is given, the checksum.
+pub fn verify_model_file(path: &Path, expected_sha1: Option<&str>) -> Result<ModelHeader> {
+    let header = ModelHeader::read(path)?;
+    if let Some(expected) = expected_sha1 {
+        let actual = compute_sha1(path)?;
+        if !actual.eq_ignore_ascii_case(expected.trim()) {
+            anyhow::bail!(
+                "SHA1 mismatch for {:?}: expected {}, got {}",
+                path,
+                expected,
+                actual
+            );
+        }
+    }
+    Ok(header)
+}
+
 /// Computes the SHA1 hash of a file.
-fn compute_sha1(path: &PathBuf) -> Result<String> {
+fn compute_sha1(path: &Path) -> Result<String> {
     use sha1::{Digest, Sha1};
 
     let mut file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
@@ -728,4 +813,53 @@ mod tests {
     fn test_default_model() {
         assert_eq!(WhisperModel::default(), WhisperModel::LargeV3TurboQ8_0);
     }
+
+    fn header(fields: [i32; 11]) -> [u8; 48] {
+        let mut bytes = [0u8; 48];
+        bytes[..4].copy_from_slice(&GGML_MAGIC.to_le_bytes());
+        for (i, field) in fields.iter().enumerate() {
+            bytes[4 + i * 4..8 + i * 4].copy_from_slice(&field.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_model_header() {
+        // large-v3-turbo
+        let turbo = header([51866, 1500, 1280, 20, 32, 448, 1280, 20, 4, 128, 8]);
+        let parsed = ModelHeader::parse(&turbo).unwrap();
+        assert_eq!((parsed.n_mels, parsed.n_text_layer), (128, 4));
+
+        let mut gguf = turbo;
+        gguf[..4].copy_from_slice(b"GGUF");
+        assert!(
+            ModelHeader::parse(&gguf)
+                .unwrap_err()
+                .to_string()
+                .contains("GGUF")
+        );
+        assert!(ModelHeader::parse(&[0; 48]).is_err());
+        // A GGML file with another architecture
+        assert!(ModelHeader::parse(&header([32000, 4096, 32, 32, 0, 0, 0, 0, 0, 0, 1])).is_err());
+    }
+
+    #[test]
+    fn test_verify_model_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ggml-custom.bin");
+        fs::write(
+            &path,
+            header([51865, 1500, 512, 8, 6, 448, 512, 8, 6, 80, 1]),
+        )
+        .unwrap();
+
+        let sha1 = compute_sha1(&path).unwrap();
+        assert!(verify_model_file(&path, None).is_ok());
+        assert!(verify_model_file(&path, Some(&sha1.to_uppercase())).is_ok());
+        assert!(verify_model_file(&path, Some("0000")).is_err());
+
+        fs::write(&path, b"short").unwrap();
+        assert!(verify_model_file(&path, None).is_err());
+        assert!(verify_model_file(&dir.path().join("missing.bin"), None).is_err());
+    }
 }
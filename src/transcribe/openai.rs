@@ -1,43 +1,311 @@
 //! OpenAI Whisper API transcription backend.
+//!
+//! Works with any server that speaks the OpenAI transcription protocol, such
+//! as whisper.cpp's server, faster-whisper-server, LocalAI or Azure OpenAI,
+//! by pointing the client at a different base URL.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
 
 use async_trait::async_trait;
 use serde::Deserialize;
 use tracing::debug;
 
-use super::{Bytes, Result, TranscribeError, Transcriber};
+use super::{
+    ApiError, Bytes, Result, Segment, TranscribeError, TranscribeOptions, Transcriber, Transcript,
+    Word, language,
+};
+use crate::core::{AuthStyle, Config, Task};
 
-const TRANSCRIPTION_ENDPOINT: &str = "https://api.openai.com/v1/audio/transcriptions";
+const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
 const DEFAULT_MODEL: &str = "gpt-4o-mini-transcribe";
+/// The only OpenAI model that translates.
+const DEFAULT_TRANSLATION_MODEL: &str = "whisper-1";
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(300);
 
 /// Configuration for the OpenAI transcription client.
 #[derive(Debug, Clone)]
 pub struct OpenAIConfig {
-    /// OpenAI API key
-    pub api_key: String,
+    /// API key, `None` for servers that don't need one
+    pub api_key: Option<String>,
+
+    /// How the API key is sent
+    pub auth_style: AuthStyle,
+
+    /// Base URL of the API (defaults to https://api.openai.com/v1)
+    pub base_url: String,
+
+    /// Organization ID, sent as `OpenAI-Organization`
+    pub organization: Option<String>,
 
-    /// Model to use (defaults to gpt-4o-mini-transcribe)
+    /// Project ID, sent as `OpenAI-Project`
+    pub project: Option<String>,
+
+    /// Extra headers sent with every request
+    pub headers: BTreeMap<String, String>,
+
+    /// Model to use (defaults to gpt-4o-mini-transcribe, or whisper-1 for
+    /// translations)
     pub model: Option<String>,
+
+    /// Format of the API response
+    pub response_format: ResponseFormat,
+
+    /// Timing detail to request, only used with [`ResponseFormat::VerboseJson`]
+    pub timestamp_granularities: Vec<TimestampGranularity>,
+
+    /// Time allowed to establish a connection
+    pub connect_timeout: Duration,
+
+    /// Time allowed for a whole request, from connecting to reading the
+    /// response
+    pub timeout: Duration,
+}
+
+/// Response format of the transcription API.
+///
+/// Only `whisper-1` supports formats other than `json` and `text`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResponseFormat {
+    /// Text only
+    #[default]
+    Json,
+    /// Plain text
+    Text,
+    /// Text with segments, words and the detected language
+    VerboseJson,
+    /// SubRip subtitles
+    Srt,
+    /// WebVTT subtitles
+    Vtt,
+}
+
+impl ResponseFormat {
+    /// Value of the `response_format` parameter.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Text => "text",
+            Self::VerboseJson => "verbose_json",
+            Self::Srt => "srt",
+            Self::Vtt => "vtt",
+        }
+    }
+}
+
+/// Timing detail of a `verbose_json` response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampGranularity {
+    /// Word timings
+    Word,
+    /// Segment timings
+    Segment,
+}
+
+impl TimestampGranularity {
+    /// Value of the `timestamp_granularities[]` parameter.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Word => "word",
+            Self::Segment => "segment",
+        }
+    }
 }
 
 impl OpenAIConfig {
     /// Create a new OpenAI config with the given API key.
     pub fn new(api_key: impl Into<String>) -> Self {
         Self {
-            api_key: api_key.into(),
+            api_key: Some(api_key.into()),
+            ..Self::keyless()
+        }
+    }
+
+    /// Create a config without an API key, for self-hosted servers.
+    pub fn keyless() -> Self {
+        Self {
+            api_key: None,
+            auth_style: AuthStyle::default(),
+            base_url: DEFAULT_BASE_URL.to_string(),
+            organization: None,
+            project: None,
+            headers: BTreeMap::new(),
             model: None,
+            response_format: ResponseFormat::default(),
+            timestamp_granularities: Vec::new(),
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            timeout: DEFAULT_TIMEOUT,
         }
     }
 
+    /// Build a config from the app config.
+    ///
+    /// An API key is required for OpenAI itself, but optional when a custom
+    /// base URL is set.
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let mut openai_config = match (config.key_openai(), &config.openai_base_url) {
+            (Some(key), _) => Self::new(key),
+            (None, Some(_)) => Self::keyless(),
+            (None, None) => return Err(TranscribeError::NoApiKey),
+        };
+        if let Some(base_url) = &config.openai_base_url {
+            openai_config = openai_config.with_base_url(base_url);
+        }
+        if let Some(model) = config.model() {
+            openai_config = openai_config.with_model(model);
+        }
+        openai_config.auth_style = config.openai_auth;
+        openai_config.organization = config.openai_organization.clone();
+        openai_config.project = config.openai_project.clone();
+        openai_config.headers = config.openai_headers.clone();
+        openai_config.connect_timeout = config.openai_connect_timeout();
+        openai_config.timeout = config.openai_timeout();
+        // Restricting detection to several languages needs the detected one
+        if config.languages().len() > 1 && openai_config.supports_verbose_json() {
+            openai_config.response_format = ResponseFormat::VerboseJson;
+        }
+        Ok(openai_config)
+    }
+
     /// Set the model to use.
     pub fn with_model(mut self, model: impl Into<String>) -> Self {
         self.model = Some(model.into());
         self
     }
 
+    /// Set the connect timeout.
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Set the total request timeout.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set the response format.
+    pub fn with_response_format(mut self, response_format: ResponseFormat) -> Self {
+        self.response_format = response_format;
+        self
+    }
+
+    /// Request segment and/or word timings.
+    ///
+    /// Switches the response format to `verbose_json`, the only one that
+    /// carries them.
+    pub fn with_timestamp_granularities(
+        mut self,
+        granularities: impl IntoIterator<Item = TimestampGranularity>,
+    ) -> Self {
+        self.response_format = ResponseFormat::VerboseJson;
+        self.timestamp_granularities = granularities.into_iter().collect();
+        self
+    }
+
+    /// Set the base URL, e.g. `http://localhost:8080/v1`.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    /// Set how the API key is sent.
+    pub fn with_auth_style(mut self, auth_style: AuthStyle) -> Self {
+        self.auth_style = auth_style;
+        self
+    }
+
+    /// Set the organization ID.
+    pub fn with_organization(mut self, organization: impl Into<String>) -> Self {
+        self.organization = Some(organization.into());
+        self
+    }
+
+    /// Set the project ID.
+    pub fn with_project(mut self, project: impl Into<String>) -> Self {
+        self.project = Some(project.into());
+        self
+    }
+
+    /// Add a header sent with every request.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
+
     /// Get the model name, using default if not set.
     pub fn model(&self) -> &str {
         self.model.as_deref().unwrap_or(DEFAULT_MODEL)
     }
+
+    /// Whether responses report the detected language, which only
+    /// [`ResponseFormat::VerboseJson`] does.
+    ///
+    /// Without it, [`TranscribeOptions::languages`] with several languages
+    /// is only passed to the model as a hint.
+    pub fn reports_language(&self) -> bool {
+        self.response_format == ResponseFormat::VerboseJson
+    }
+
+    /// Whether the model can respond with [`ResponseFormat::VerboseJson`].
+    ///
+    /// Whisper models can, `gpt-4o-transcribe` and its siblings only
+    /// return text.
+    pub fn supports_verbose_json(&self) -> bool {
+        self.model().contains("whisper")
+    }
+
+    /// Get the model for `task`, using the default for it if not set.
+    pub fn model_for(&self, task: Task) -> &str {
+        match (&self.model, task) {
+            (Some(model), _) => model,
+            (None, Task::Transcribe) => DEFAULT_MODEL,
+            (None, Task::Translate) => DEFAULT_TRANSLATION_MODEL,
+        }
+    }
+
+    /// Get the transcription endpoint URL.
+    pub fn transcription_url(&self) -> String {
+        format!(
+            "{}/audio/transcriptions",
+            self.base_url.trim_end_matches('/')
+        )
+    }
+
+    /// Get the translation endpoint URL.
+    pub fn translation_url(&self) -> String {
+        format!("{}/audio/translations", self.base_url.trim_end_matches('/'))
+    }
+
+    /// Get the endpoint URL for `task`.
+    pub fn url_for(&self, task: Task) -> String {
+        match task {
+            Task::Transcribe => self.transcription_url(),
+            Task::Translate => self.translation_url(),
+        }
+    }
+
+    /// Add authentication and custom headers to a request.
+    fn authorize(&self, mut request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        if let Some(key) = &self.api_key {
+            request = match self.auth_style {
+                AuthStyle::Bearer => request.bearer_auth(key),
+                AuthStyle::ApiKey => request.header("api-key", key),
+            };
+        }
+        if let Some(organization) = &self.organization {
+            request = request.header("OpenAI-Organization", organization);
+        }
+        if let Some(project) = &self.project {
+            request = request.header("OpenAI-Project", project);
+        }
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        request
+    }
 }
 
 /// OpenAI Whisper API client.
@@ -47,79 +315,805 @@ pub struct OpenAIClient {
     config: OpenAIConfig,
 }
 
+/// Transcription response.
+///
+/// Only `text` is always present, the rest comes with `verbose_json`.
 #[derive(Debug, Deserialize)]
 struct WhisperResponse {
     text: String,
+    #[serde(default)]
+    language: Option<String>,
+    #[serde(default)]
+    segments: Vec<ResponseSegment>,
+    #[serde(default)]
+    words: Vec<ResponseWord>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponseSegment {
+    text: String,
+    start: f64,
+    end: f64,
+    #[serde(default)]
+    avg_logprob: Option<f32>,
+    #[serde(default)]
+    no_speech_prob: Option<f32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponseWord {
+    word: String,
+    start: f64,
+    end: f64,
+}
+
+impl WhisperResponse {
+    /// Convert to a transcript, assigning words to the segment they start in.
+    ///
+    /// Responses with only word timings get a single segment spanning all
+    /// words.
+    fn into_transcript(self, backend: &str, model: &str) -> Transcript {
+        let secs = |s: f64| Duration::from_secs_f64(s.max(0.0));
+        let mut segments: Vec<Segment> = self
+            .segments
+            .into_iter()
+            .map(|s| Segment {
+                avg_logprob: s.avg_logprob,
+                no_speech_prob: s.no_speech_prob,
+                ..Segment::new(s.text, secs(s.start), secs(s.end))
+            })
+            .collect();
+
+        if segments.is_empty()
+            && let (Some(first), Some(last)) = (self.words.first(), self.words.last())
+        {
+            segments.push(Segment::new(
+                self.text.trim(),
+                secs(first.start),
+                secs(last.end),
+            ));
+        }
+
+        for word in self.words {
+            let word = Word {
+                text: word.word.trim().to_string(),
+                start: secs(word.start),
+                end: secs(word.end),
+                probability: None,
+            };
+            let index = segments
+                .iter()
+                .rposition(|s| s.start <= word.start)
+                .unwrap_or(0);
+            if let Some(segment) = segments.get_mut(index) {
+                segment.words.push(word);
+            }
+        }
+
+        let mut transcript = Transcript::new(self.text, backend)
+            .with_segments(segments)
+            .with_model(model);
+        // Reported as an English name, e.g. "english"
+        if let Some(language) = self.language {
+            let code = language::code(&language).map(str::to_string);
+            transcript = transcript.with_language(code.unwrap_or(language));
+        }
+        transcript
+    }
 }
 
 impl OpenAIClient {
     /// Create a new OpenAI client with the given configuration.
     pub fn new(config: OpenAIConfig) -> Self {
-        Self {
-            client: reqwest::Client::new(),
-            config,
-        }
+        let client = reqwest::Client::builder()
+            .connect_timeout(config.connect_timeout)
+            .build()
+            .expect("Failed to build HTTP client");
+        Self { client, config }
     }
 
     /// Create a client from just an API key with default settings.
     pub fn from_api_key(api_key: impl Into<String>) -> Self {
         Self::new(OpenAIConfig::new(api_key))
     }
-}
 
-#[async_trait]
-impl Transcriber for OpenAIClient {
-    async fn transcribe(&self, audio: Bytes, language: Option<&str>) -> Result<String> {
-        debug!(
-            model = self.config.model(),
-            audio_bytes = audio.len(),
-            language = ?language,
-            "Sending transcription request to OpenAI"
-        );
+    /// Send a transcription or translation request and parse the response.
+    async fn send(&self, form: reqwest::multipart::Form, task: Task) -> Result<Transcript> {
+        let model = self.config.model_for(task);
+        let response = self
+            .config
+            .authorize(self.client.post(self.config.url_for(task)))
+            .timeout(self.config.timeout)
+            .multipart(form)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(ApiError::from_response(response).await.into());
+        }
 
+        let transcript = match self.config.response_format {
+            ResponseFormat::Json | ResponseFormat::VerboseJson => response
+                .json::<WhisperResponse>()
+                .await
+                .map_err(|e| TranscribeError::TranscriptionFailed(e.to_string()))?
+                .into_transcript(self.name(), model),
+            ResponseFormat::Text => {
+                let body = response.text().await?;
+                Transcript::new(body.trim(), self.name()).with_model(model)
+            }
+            ResponseFormat::Srt | ResponseFormat::Vtt => {
+                let body = response.text().await?;
+                let segments = parse_subtitles(&body);
+                let text = segments
+                    .iter()
+                    .map(|s| s.text.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                Transcript::new(text, self.name())
+                    .with_segments(segments)
+                    .with_model(model)
+                    .with_raw(body)
+            }
+        };
+        Ok(transcript)
+    }
+}
+
+impl OpenAIClient {
+    /// Build the request form, asking for `language` if given.
+    ///
+    /// Translations take neither a language nor timestamp granularities.
+    fn form(
+        &self,
+        audio: Bytes,
+        options: &TranscribeOptions,
+        language: Option<&str>,
+    ) -> Result<reqwest::multipart::Form> {
         let mut form = reqwest::multipart::Form::new()
             .part(
                 "file",
                 reqwest::multipart::Part::stream(reqwest::Body::from(audio))
                     .file_name("recording.wav")
                     .mime_str("audio/wav")
-                    .map_err(|e| TranscribeError::ApiError(e.to_string()))?,
+                    .map_err(|e| TranscribeError::InvalidAudioFormat(e.to_string()))?,
             )
             .part(
                 "model",
-                reqwest::multipart::Part::text(self.config.model().to_string()),
+                reqwest::multipart::Part::text(self.config.model_for(options.task).to_string()),
             );
 
-        if let Some(lang) = language {
-            form = form.part("language", reqwest::multipart::Part::text(lang.to_string()));
+        let response_format = self.config.response_format;
+        if response_format != ResponseFormat::Json {
+            form = form.part(
+                "response_format",
+                reqwest::multipart::Part::text(response_format.as_str()),
+            );
         }
+        if options.task == Task::Transcribe {
+            for granularity in &self.config.timestamp_granularities {
+                form = form.part(
+                    "timestamp_granularities[]",
+                    reqwest::multipart::Part::text(granularity.as_str()),
+                );
+            }
+            if let Some(lang) = language {
+                form = form.part("language", reqwest::multipart::Part::text(lang.to_string()));
+            }
+        }
+        if let Some(prompt) = options.prompt() {
+            form = form.part("prompt", reqwest::multipart::Part::text(prompt.to_string()));
+        }
+        if let Some(temperature) = options.temperature {
+            form = form.part(
+                "temperature",
+                reqwest::multipart::Part::text(temperature.to_string()),
+            );
+        }
+        Ok(form)
+    }
 
-        let response = self
-            .client
-            .post(TRANSCRIPTION_ENDPOINT)
-            .header("Authorization", format!("Bearer {}", self.config.api_key))
-            .multipart(form)
-            .send()
-            .await?;
+    /// Send `form`, failing with [`TranscribeError::Cancelled`] once the
+    /// request is cancelled.
+    async fn send_cancellable(
+        &self,
+        form: reqwest::multipart::Form,
+        options: &TranscribeOptions,
+    ) -> Result<Transcript> {
+        options
+            .cancellation
+            .run_until_cancelled(self.send(form, options.task))
+            .await
+            .unwrap_or(Err(TranscribeError::Cancelled))
+    }
+}
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            return Err(TranscribeError::ApiError(format!(
-                "API returned {}: {}",
-                status, body
-            )));
+#[async_trait]
+impl Transcriber for OpenAIClient {
+    /// Transcribe `audio`, restricting the language to
+    /// [`TranscribeOptions::languages`] if set.
+    ///
+    /// The API can't restrict auto-detection to a set of languages. A
+    /// single allowed language is sent as the language. With several, when
+    /// the API reports a language outside the set the audio is uploaded
+    /// again for each allowed language, keeping the most confident
+    /// transcript. Only `verbose_json` responses report the language, see
+    /// [`OpenAIConfig::reports_language`]; otherwise the allowed languages
+    /// are named in the prompt as a hint.
+    ///
+    /// Translations go to the translations endpoint, which detects the
+    /// language itself.
+    async fn transcribe(&self, audio: Bytes, options: &TranscribeOptions) -> Result<Transcript> {
+        if options.task == Task::Translate {
+            debug!(
+                url = self.config.translation_url(),
+                model = self.config.model_for(Task::Translate),
+                audio_bytes = audio.len(),
+                prompt = ?options.prompt,
+                temperature = ?options.temperature,
+                "Sending translation request to OpenAI"
+            );
+            let form = self.form(audio, options, None)?;
+            return self.send_cancellable(form, options).await;
         }
 
-        let whisper_response: WhisperResponse = response
-            .json()
-            .await
-            .map_err(|e| TranscribeError::TranscriptionFailed(e.to_string()))?;
+        let allowed = &options.languages;
+        let language = options.language().or(match allowed.as_slice() {
+            [only] => Some(only.as_str()),
+            _ => None,
+        });
+        let hinted;
+        let options = match language_hint(allowed) {
+            Some(hint) if language.is_none() && !self.config.reports_language() => {
+                let prompt = match options.prompt() {
+                    Some(prompt) => format!("{hint} {prompt}"),
+                    None => hint,
+                };
+                hinted = options.clone().with_prompt(prompt);
+                &hinted
+            }
+            _ => options,
+        };
+        debug!(
+            url = self.config.transcription_url(),
+            model = self.config.model(),
+            audio_bytes = audio.len(),
+            language = ?language,
+            prompt = ?options.prompt,
+            temperature = ?options.temperature,
+            "Sending transcription request to OpenAI"
+        );
 
-        Ok(whisper_response.text)
+        let form = self.form(audio.clone(), options, language)?;
+        let mut transcript = self.send_cancellable(form, options).await?;
+        if language.is_none()
+            && !allowed.is_empty()
+            && let Some(detected) = transcript.language.as_deref()
+            && !language::is_allowed(detected, allowed)
+        {
+            debug!(
+                detected,
+                allowed = ?allowed,
+                "Detected language isn't allowed, transcribing in each allowed one"
+            );
+            let mut best: Option<Transcript> = None;
+            for candidate in allowed {
+                let form = self.form(audio.clone(), options, Some(candidate))?;
+                let mut retry = self.send_cancellable(form, options).await?;
+                if retry.language.is_none() {
+                    retry = retry.with_language(candidate.clone());
+                }
+                if best
+                    .as_ref()
+                    .is_none_or(|best| confidence(&retry) > confidence(best))
+                {
+                    best = Some(retry);
+                }
+            }
+            transcript = best.unwrap_or(transcript);
+        }
+        if transcript.language.is_none()
+            && let Some(lang) = language
+        {
+            transcript = transcript.with_language(lang);
+        }
+        Ok(transcript)
     }
 
     fn name(&self) -> &str {
         "openai"
     }
 }
+
+/// Parse the cues of SRT or WebVTT subtitles into segments.
+///
+/// Cue numbers, the WebVTT header, notes and cue settings are skipped.
+fn parse_subtitles(subtitles: &str) -> Vec<Segment> {
+    let subtitles = subtitles.replace("\r\n", "\n");
+    subtitles
+        .split("\n\n")
+        .filter_map(|block| {
+            let mut lines = block.lines().skip_while(|line| !line.contains("-->"));
+            let (start, end) = lines.next()?.split_once("-->")?;
+            let start = parse_timestamp(start.trim())?;
+            // WebVTT cue settings follow the end timestamp
+            let end = parse_timestamp(end.split_whitespace().next()?)?;
+            let text = lines
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .collect::<Vec<_>>()
+                .join(" ");
+            Some(Segment::new(text, start, end))
+        })
+        .collect()
+}
+
+/// Parse a subtitle timestamp: `HH:MM:SS,mmm` (SRT) or `[HH:]MM:SS.mmm`
+/// (WebVTT).
+fn parse_timestamp(timestamp: &str) -> Option<Duration> {
+    let (clock, millis) = timestamp.split_once([',', '.'])?;
+    let secs = clock
+        .split(':')
+        .try_fold(0u64, |acc, part| Some(acc * 60 + part.parse::<u64>().ok()?))?;
+    Some(Duration::from_secs(secs) + Duration::from_millis(millis.parse().ok()?))
+}
+
+/// Prompt naming the allowed languages, when there are several.
+fn language_hint(allowed: &[String]) -> Option<String> {
+    let mut names: Vec<String> = allowed
+        .iter()
+        .filter_map(|code| language::name(code))
+        .map(|name| {
+            let mut chars = name.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        })
+        .collect();
+    let last = names.pop()?;
+    if names.is_empty() {
+        return None;
+    }
+    Some(format!("The audio is in {} or {last}.", names.join(", ")))
+}
+
+/// Mean log-probability of the transcript's segments, lowest if unknown.
+fn confidence(transcript: &Transcript) -> f32 {
+    let logprobs: Vec<f32> = transcript
+        .segments
+        .iter()
+        .filter_map(|s| s.avg_logprob)
+        .collect();
+    if logprobs.is_empty() {
+        return f32::NEG_INFINITY;
+    }
+    logprobs.iter().sum::<f32>() / logprobs.len() as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::matchers::{body_string_contains, header, method, path};
+    use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+    use super::*;
+
+    fn text_response(text: &str) -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({ "text": text }))
+    }
+
+    #[tokio::test]
+    async fn test_custom_server_with_headers() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/audio/transcriptions"))
+            .and(header("authorization", "Bearer test-key"))
+            .and(header("openai-organization", "org-1"))
+            .and(header("openai-project", "proj-1"))
+            .and(header("x-team", "audio"))
+            .respond_with(text_response("Hello."))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = OpenAIConfig::new("test-key")
+            .with_base_url(format!("{}/v1/", server.uri()))
+            .with_organization("org-1")
+            .with_project("proj-1")
+            .with_header("X-Team", "audio");
+        let transcript = OpenAIClient::new(config)
+            .transcribe(Bytes::from_static(b"RIFF"), &TranscribeOptions::default())
+            .await
+            .unwrap();
+
+        assert_eq!(transcript.text, "Hello.");
+    }
+
+    #[tokio::test]
+    async fn test_sends_prompt_and_temperature() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_string_contains("name=\"language\"\r\n\r\nde\r\n"))
+            .and(body_string_contains(
+                "name=\"prompt\"\r\n\r\nWhisp, GitHub.\r\n",
+            ))
+            .and(body_string_contains("name=\"temperature\"\r\n\r\n0.2\r\n"))
+            .respond_with(text_response("Hallo."))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = Config {
+            openai_key: Some("test-key".to_string()),
+            openai_base_url: Some(server.uri()),
+            language: Some("de".to_string()),
+            prompt: Some("Whisp, GitHub.".to_string()),
+            temperature: Some(0.2),
+            ..Default::default()
+        };
+        let transcript = OpenAIClient::new(OpenAIConfig::from_config(&config).unwrap())
+            .transcribe(
+                Bytes::from_static(b"RIFF"),
+                &TranscribeOptions::from_config(&config),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(transcript.language.as_deref(), Some("de"));
+    }
+
+    #[tokio::test]
+    async fn test_translate() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/audio/translations"))
+            .and(body_string_contains("name=\"model\"\r\n\r\nwhisper-1\r\n"))
+            .and(|request: &Request| {
+                !String::from_utf8_lossy(&request.body).contains("name=\"language\"")
+            })
+            .respond_with(text_response("Good morning."))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = Config {
+            openai_key: Some("test-key".to_string()),
+            openai_base_url: Some(server.uri()),
+            language: Some("de".to_string()),
+            task: Task::Translate,
+            ..Default::default()
+        };
+        let transcript = OpenAIClient::new(OpenAIConfig::from_config(&config).unwrap())
+            .transcribe(
+                Bytes::from_static(b"RIFF"),
+                &TranscribeOptions::from_config(&config),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(transcript.text, "Good morning.");
+        assert_eq!(transcript.model.as_deref(), Some("whisper-1"));
+    }
+
+    #[tokio::test]
+    async fn test_verbose_json_with_word_timings() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_string_contains(
+                "name=\"response_format\"\r\n\r\nverbose_json\r\n",
+            ))
+            .and(body_string_contains(
+                "name=\"timestamp_granularities[]\"\r\n\r\nword\r\n",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "text": "Hello there.",
+                "words": [
+                    {"word": "Hello", "start": 0.0, "end": 0.5},
+                    {"word": "there.", "start": 0.6, "end": 1.2}
+                ]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = OpenAIConfig::new("test-key")
+            .with_base_url(server.uri())
+            .with_model("whisper-1")
+            .with_timestamp_granularities([TimestampGranularity::Word]);
+        let transcript = OpenAIClient::new(config)
+            .transcribe(Bytes::from_static(b"RIFF"), &TranscribeOptions::default())
+            .await
+            .unwrap();
+
+        assert_eq!(transcript.segments.len(), 1);
+        assert_eq!(transcript.segments[0].end, Duration::from_millis(1200));
+        assert_eq!(transcript.words().count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_retries_in_allowed_language() {
+        let verbose = |text: &str, language: &str, avg_logprob: f32| {
+            ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "text": text,
+                "language": language,
+                "segments": [
+                    {"text": text, "start": 0.0, "end": 1.0, "avg_logprob": avg_logprob}
+                ]
+            }))
+        };
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_string_contains("name=\"language\"\r\n\r\nen\r\n"))
+            .respond_with(verbose("Hollow.", "english", -0.9))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_string_contains("name=\"language\"\r\n\r\nde\r\n"))
+            .respond_with(verbose("Hallo.", "german", -0.2))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(verbose("Helo.", "welsh", -0.5))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = Config {
+            openai_key: Some("test-key".to_string()),
+            openai_base_url: Some(server.uri()),
+            model: Some("whisper-1".to_string()),
+            languages: vec!["en".to_string(), "de".to_string()],
+            ..Default::default()
+        };
+        let openai_config = OpenAIConfig::from_config(&config).unwrap();
+        assert!(openai_config.reports_language());
+        let transcript = OpenAIClient::new(openai_config)
+            .transcribe(
+                Bytes::from_static(b"RIFF"),
+                &TranscribeOptions::from_config(&config),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(transcript.text, "Hallo.");
+        assert_eq!(transcript.language.as_deref(), Some("de"));
+    }
+
+    #[tokio::test]
+    async fn test_hints_allowed_languages() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_string_contains(
+                "name=\"prompt\"\r\n\r\nThe audio is in English or German. Whisp.\r\n",
+            ))
+            .and(|request: &Request| {
+                !String::from_utf8_lossy(&request.body).contains("name=\"response_format\"")
+            })
+            .respond_with(text_response("Hallo."))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = Config {
+            openai_key: Some("test-key".to_string()),
+            openai_base_url: Some(server.uri()),
+            languages: vec!["en".to_string(), "de".to_string()],
+            prompt: Some("Whisp.".to_string()),
+            ..Default::default()
+        };
+        let openai_config = OpenAIConfig::from_config(&config).unwrap();
+        assert!(!openai_config.reports_language());
+        let transcript = OpenAIClient::new(openai_config)
+            .transcribe(
+                Bytes::from_static(b"RIFF"),
+                &TranscribeOptions::from_config(&config),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(transcript.text, "Hallo.");
+    }
+
+    #[tokio::test]
+    async fn test_srt_passed_through() {
+        let srt = "1\n00:00:00,000 --> 00:00:01,500\nHello there.\n\n\
+                   2\n00:00:01,500 --> 00:00:02,000\nBye.\n";
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_string_contains(
+                "name=\"response_format\"\r\n\r\nsrt\r\n",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_string(srt))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = OpenAIConfig::new("test-key")
+            .with_base_url(server.uri())
+            .with_response_format(ResponseFormat::Srt);
+        let transcript = OpenAIClient::new(config)
+            .transcribe(Bytes::from_static(b"RIFF"), &TranscribeOptions::default())
+            .await
+            .unwrap();
+
+        assert_eq!(transcript.raw.as_deref(), Some(srt));
+        assert_eq!(transcript.text, "Hello there. Bye.");
+        assert_eq!(transcript.segments[1].start, Duration::from_millis(1500));
+    }
+
+    #[tokio::test]
+    async fn test_request_timeout() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(text_response("Hello.").set_delay(Duration::from_secs(5)))
+            .mount(&server)
+            .await;
+
+        let config = OpenAIConfig::new("test-key")
+            .with_base_url(server.uri())
+            .with_timeout(Duration::from_millis(100));
+        let result = OpenAIClient::new(config)
+            .transcribe(Bytes::from_static(b"RIFF"), &TranscribeOptions::default())
+            .await;
+
+        let Err(error @ TranscribeError::NetworkError(_)) = result else {
+            panic!("expected a network error, got {:?}", result);
+        };
+        assert!(error.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_in_flight_request() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(text_response("Hello.").set_delay(Duration::from_secs(5)))
+            .mount(&server)
+            .await;
+
+        let client = OpenAIClient::new(OpenAIConfig::new("test-key").with_base_url(server.uri()));
+        let options = TranscribeOptions::default();
+        let cancellation = options.cancellation.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            cancellation.cancel();
+        });
+        let result = client
+            .transcribe(Bytes::from_static(b"RIFF"), &options)
+            .await;
+
+        assert!(matches!(result, Err(TranscribeError::Cancelled)));
+    }
+
+    #[tokio::test]
+    async fn test_api_key_auth_style() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(header("api-key", "azure-key"))
+            .and(|req: &Request| !req.headers.contains_key("authorization"))
+            .respond_with(text_response("Hello."))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = OpenAIConfig::new("azure-key")
+            .with_base_url(server.uri())
+            .with_auth_style(AuthStyle::ApiKey);
+        let result = OpenAIClient::new(config)
+            .transcribe(Bytes::from_static(b"RIFF"), &TranscribeOptions::default())
+            .await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_keyless_server() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(|req: &Request| {
+                !req.headers.contains_key("authorization") && !req.headers.contains_key("api-key")
+            })
+            .respond_with(text_response("Hello."))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = Config {
+            openai_base_url: Some(server.uri()),
+            ..Default::default()
+        };
+        let result = OpenAIClient::new(OpenAIConfig::from_config(&config).unwrap())
+            .transcribe(Bytes::from_static(b"RIFF"), &TranscribeOptions::default())
+            .await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_error_status() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(401).set_body_string("bad key"))
+            .mount(&server)
+            .await;
+
+        let config = OpenAIConfig::new("wrong").with_base_url(server.uri());
+        let result = OpenAIClient::new(config)
+            .transcribe(Bytes::from_static(b"RIFF"), &TranscribeOptions::default())
+            .await;
+
+        let Err(TranscribeError::Api(error)) = result else {
+            panic!("expected an API error, got {:?}", result);
+        };
+        assert_eq!(error.status, reqwest::StatusCode::UNAUTHORIZED);
+        assert_eq!(error.message, "bad key");
+        assert!(!error.is_retryable());
+    }
+
+    #[test]
+    fn test_from_config_requires_key_for_openai() {
+        let result = OpenAIConfig::from_config(&Config::default());
+
+        assert!(matches!(result, Err(TranscribeError::NoApiKey)));
+    }
+
+    #[test]
+    fn test_text_response() {
+        let response: WhisperResponse = serde_json::from_str(r#"{"text": "Hello."}"#).unwrap();
+
+        let transcript = response.into_transcript("openai", "gpt-4o-mini-transcribe");
+
+        assert_eq!(transcript.text, "Hello.");
+        assert!(transcript.segments.is_empty());
+        assert_eq!(transcript.model.as_deref(), Some("gpt-4o-mini-transcribe"));
+    }
+
+    #[test]
+    fn test_verbose_response() {
+        let response: WhisperResponse = serde_json::from_str(
+            r#"{
+                "text": "Hello there. Bye.",
+                "language": "english",
+                "segments": [
+                    {"id": 0, "text": " Hello there.", "start": 0.0, "end": 1.5,
+                     "avg_logprob": -0.25, "no_speech_prob": 0.01},
+                    {"id": 1, "text": " Bye.", "start": 1.5, "end": 2.0,
+                     "avg_logprob": -0.5, "no_speech_prob": 0.02}
+                ],
+                "words": [
+                    {"word": "Hello", "start": 0.0, "end": 0.5},
+                    {"word": "there", "start": 0.6, "end": 1.4},
+                    {"word": "Bye", "start": 1.6, "end": 1.9}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let transcript = response.into_transcript("openai", "whisper-1");
+
+        assert_eq!(transcript.language.as_deref(), Some("en"));
+        assert_eq!(transcript.segments.len(), 2);
+        assert_eq!(transcript.segments[0].avg_logprob, Some(-0.25));
+        assert_eq!(transcript.segments[0].words.len(), 2);
+        assert_eq!(transcript.segments[1].words[0].text, "Bye");
+        assert_eq!(transcript.segments[1].end, Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_parse_vtt() {
+        let vtt = "WEBVTT\r\n\r\nNOTE generated\r\n\r\n\
+                   intro\r\n01:02.250 --> 01:04.000 align:start\r\nHello\r\nthere.\r\n";
+
+        let segments = parse_subtitles(vtt);
+
+        assert_eq!(
+            segments,
+            vec![Segment::new(
+                "Hello there.",
+                Duration::from_millis(62_250),
+                Duration::from_secs(64),
+            )]
+        );
+    }
+}
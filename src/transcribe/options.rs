@@ -0,0 +1,121 @@
+//! Per-request transcription options.
+
+use tokio_util::sync::CancellationToken;
+
+use crate::core::{Config, Task};
+
+/// Options for a single transcription request.
+///
+/// Every option is a hint; unset options leave the backend's default in
+/// place.
+#[derive(Debug, Clone, Default)]
+pub struct TranscribeOptions {
+    /// Language hint (ISO 639-1 code, e.g., "en")
+    pub language: Option<String>,
+    /// Languages auto-detection may pick from when `language` isn't set,
+    /// all languages if empty
+    pub languages: Vec<String>,
+    /// Text to condition the model on, e.g. product names or the desired
+    /// punctuation style
+    pub prompt: Option<String>,
+    /// Sampling temperature, 0 for the most deterministic output
+    pub temperature: Option<f32>,
+    /// Transcribe, or translate to English
+    pub task: Task,
+    /// Cancels the request, which then fails with
+    /// [`TranscribeError::Cancelled`](super::TranscribeError::Cancelled)
+    pub cancellation: CancellationToken,
+}
+
+impl TranscribeOptions {
+    /// Create options with every setting left to the backend.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take the options from the app config.
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            language: config.language().map(str::to_string),
+            languages: Vec::new(),
+            prompt: config.prompt().map(str::to_string),
+            temperature: config.temperature(),
+            task: config.task,
+            cancellation: CancellationToken::new(),
+        }
+        .with_languages(config.languages())
+    }
+
+    /// Set the language hint.
+    pub fn with_language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+
+    /// Set the languages auto-detection may pick from.
+    ///
+    /// Codes are lowercased, the form whisper.cpp and the OpenAI API
+    /// accept.
+    pub fn with_languages(mut self, languages: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        self.languages = languages
+            .into_iter()
+            .map(|code| code.as_ref().to_ascii_lowercase())
+            .collect();
+        self
+    }
+
+    /// Set the prompt.
+    pub fn with_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.prompt = Some(prompt.into());
+        self
+    }
+
+    /// Set the sampling temperature.
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    /// Set whether to transcribe or translate to English.
+    pub fn with_task(mut self, task: Task) -> Self {
+        self.task = task;
+        self
+    }
+
+    /// Set the token that cancels the request.
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    /// Get the language hint.
+    pub fn language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+
+    /// Get the prompt.
+    pub fn prompt(&self) -> Option<&str> {
+        self.prompt.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lowercases_languages() {
+        let config = Config {
+            languages: vec!["EN".to_string(), "De".to_string()],
+            ..Config::default()
+        };
+        assert_eq!(
+            TranscribeOptions::from_config(&config).languages,
+            ["en", "de"]
+        );
+        assert_eq!(
+            TranscribeOptions::new().with_languages(["Fr"]).languages,
+            ["fr"]
+        );
+    }
+}
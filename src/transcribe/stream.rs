@@ -0,0 +1,271 @@
+//! Streaming transcription types.
+//!
+//! A streaming transcription takes audio as it is recorded and yields
+//! transcript segments while the speaker is still talking. Backends that
+//! can't stream fall back to [`buffered`], which waits for the end of the
+//! audio and transcribes it in one go.
+
+use std::io::Cursor;
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures_util::stream::{self, BoxStream, StreamExt};
+use hound::{SampleFormat, WavSpec, WavWriter};
+
+use super::{Result, Segment, TranscribeError, TranscribeOptions, Transcriber};
+
+/// A chunk of interleaved f32 audio samples.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioChunk {
+    /// Interleaved samples
+    pub samples: Vec<f32>,
+    /// Sample rate in Hz
+    pub sample_rate: u32,
+    /// Number of interleaved channels
+    pub channels: u16,
+}
+
+impl AudioChunk {
+    /// Create a chunk from interleaved samples.
+    pub fn new(samples: Vec<f32>, sample_rate: u32, channels: u16) -> Self {
+        Self {
+            samples,
+            sample_rate,
+            channels,
+        }
+    }
+
+    /// Duration of the audio in this chunk.
+    pub fn duration(&self) -> Duration {
+        let frames = self.samples.len() / self.channels.max(1) as usize;
+        Duration::from_secs_f64(frames as f64 / self.sample_rate as f64)
+    }
+
+    /// Mix the chunk down to mono.
+    pub fn to_mono(&self) -> Vec<f32> {
+        let channels = self.channels.max(1) as usize;
+        if channels == 1 {
+            return self.samples.clone();
+        }
+        self.samples
+            .chunks(channels)
+            .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+            .collect()
+    }
+}
+
+/// A piece of transcript yielded by a streaming transcription.
+///
+/// Partial segments are provisional: each one replaces the partial segment
+/// before it. Final segments are never revised. Timestamps are relative to
+/// the start of the stream.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamSegment {
+    /// The transcribed segment
+    pub segment: Segment,
+    /// Whether this segment is final
+    pub is_final: bool,
+}
+
+impl StreamSegment {
+    /// Create a final segment.
+    pub fn final_segment(segment: Segment) -> Self {
+        Self {
+            segment,
+            is_final: true,
+        }
+    }
+
+    /// Create a partial segment.
+    pub fn partial(segment: Segment) -> Self {
+        Self {
+            segment,
+            is_final: false,
+        }
+    }
+}
+
+/// Audio fed into a streaming transcription.
+pub type AudioStream = BoxStream<'static, AudioChunk>;
+
+/// Segments yielded by a streaming transcription.
+pub type SegmentStream<'a> = BoxStream<'a, Result<StreamSegment>>;
+
+/// Streaming adapter for backends that only transcribe complete audio.
+///
+/// Collects the whole audio stream, then yields the transcript's segments as
+/// final segments, or a single segment spanning the audio if the backend
+/// doesn't report any. An empty audio stream yields nothing.
+pub fn buffered<'a, T>(
+    transcriber: &'a T,
+    audio: AudioStream,
+    options: &'a TranscribeOptions,
+) -> SegmentStream<'a>
+where
+    T: Transcriber + ?Sized,
+{
+    stream::once(async move {
+        let chunks: Vec<AudioChunk> = audio.collect().await;
+        let Some((wav, duration)) = encode_wav(&chunks)? else {
+            return Ok(Vec::new());
+        };
+
+        let transcript = transcriber.transcribe(wav, options).await?;
+        let segments = if transcript.segments.is_empty() {
+            vec![Segment::new(transcript.text, Duration::ZERO, duration)]
+        } else {
+            transcript.segments
+        };
+        Ok(segments)
+    })
+    .flat_map(|result: Result<Vec<Segment>>| {
+        let segments: Vec<Result<StreamSegment>> = match result {
+            Ok(segments) => segments
+                .into_iter()
+                .map(|s| Ok(StreamSegment::final_segment(s)))
+                .collect(),
+            Err(e) => vec![Err(e)],
+        };
+        stream::iter(segments)
+    })
+    .boxed()
+}
+
+/// Encode chunks as a single WAV file, returning it with its duration.
+///
+/// Returns `None` if there are no chunks.
+fn encode_wav(chunks: &[AudioChunk]) -> Result<Option<(Bytes, Duration)>> {
+    let Some(first) = chunks.first() else {
+        return Ok(None);
+    };
+    let spec = WavSpec {
+        channels: first.channels,
+        sample_rate: first.sample_rate,
+        bits_per_sample: 32,
+        sample_format: SampleFormat::Float,
+    };
+
+    let mut cursor = Cursor::new(Vec::new());
+    let mut duration = Duration::ZERO;
+    {
+        let mut writer = WavWriter::new(&mut cursor, spec)
+            .map_err(|e| TranscribeError::InvalidAudioFormat(e.to_string()))?;
+        for chunk in chunks {
+            if chunk.sample_rate != spec.sample_rate || chunk.channels != spec.channels {
+                return Err(TranscribeError::InvalidAudioFormat(format!(
+                    "Audio stream changed format from {} Hz/{} ch to {} Hz/{} ch",
+                    spec.sample_rate, spec.channels, chunk.sample_rate, chunk.channels
+                )));
+            }
+            duration += chunk.duration();
+            for &sample in &chunk.samples {
+                writer
+                    .write_sample(sample)
+                    .map_err(|e| TranscribeError::InvalidAudioFormat(e.to_string()))?;
+            }
+        }
+        writer
+            .finalize()
+            .map_err(|e| TranscribeError::InvalidAudioFormat(e.to_string()))?;
+    }
+
+    Ok(Some((Bytes::from(cursor.into_inner()), duration)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use async_trait::async_trait;
+    use futures_util::TryStreamExt;
+
+    use super::*;
+    use crate::transcribe::Transcript;
+
+    /// Transcriber that records the audio it was given.
+    #[derive(Default)]
+    struct Recorder {
+        audio: Mutex<Vec<Bytes>>,
+    }
+
+    #[async_trait]
+    impl Transcriber for Recorder {
+        async fn transcribe(
+            &self,
+            audio: Bytes,
+            _options: &TranscribeOptions,
+        ) -> Result<Transcript> {
+            self.audio.lock().unwrap().push(audio);
+            Ok(Transcript::new("hello world", "recorder"))
+        }
+
+        fn name(&self) -> &str {
+            "recorder"
+        }
+    }
+
+    fn audio(chunks: Vec<AudioChunk>) -> AudioStream {
+        stream::iter(chunks).boxed()
+    }
+
+    #[tokio::test]
+    async fn test_buffered_yields_single_final_segment() {
+        let transcriber = Recorder::default();
+        let chunks = vec![
+            AudioChunk::new(vec![0.1; 16000], 16000, 2),
+            AudioChunk::new(vec![0.2; 16000], 16000, 2),
+        ];
+
+        let segments: Vec<StreamSegment> = transcriber
+            .transcribe_stream(audio(chunks), &TranscribeOptions::default())
+            .try_collect()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            segments,
+            vec![StreamSegment::final_segment(Segment::new(
+                "hello world",
+                Duration::ZERO,
+                Duration::from_secs(1),
+            ))]
+        );
+        let sent = transcriber.audio.lock().unwrap();
+        let reader = hound::WavReader::new(Cursor::new(&sent[0][..])).unwrap();
+        assert_eq!(reader.spec().channels, 2);
+        assert_eq!(reader.duration(), 16000);
+    }
+
+    #[tokio::test]
+    async fn test_buffered_empty_stream() {
+        let transcriber = Recorder::default();
+
+        let segments: Vec<StreamSegment> = transcriber
+            .transcribe_stream(audio(Vec::new()), &TranscribeOptions::default())
+            .try_collect()
+            .await
+            .unwrap();
+
+        assert!(segments.is_empty());
+        assert!(transcriber.audio.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_buffered_rejects_format_change() {
+        let transcriber = Recorder::default();
+        let chunks = vec![
+            AudioChunk::new(vec![0.1; 160], 16000, 1),
+            AudioChunk::new(vec![0.1; 480], 48000, 1),
+        ];
+
+        let result: Result<Vec<StreamSegment>> = transcriber
+            .transcribe_stream(audio(chunks), &TranscribeOptions::default())
+            .try_collect()
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(TranscribeError::InvalidAudioFormat(_))
+        ));
+    }
+}
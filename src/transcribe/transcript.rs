@@ -0,0 +1,266 @@
+//! Transcription results.
+//!
+//! Backends report as much detail as they have: at the very least the text
+//! and which backend produced it, and where available segment timestamps,
+//! word timings, confidence and the detected language. Fields a backend
+//! can't provide are left empty.
+
+use std::time::Duration;
+
+/// The result of a transcription.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transcript {
+    /// Full transcribed text
+    pub text: String,
+    /// Timed segments of the text, in order
+    pub segments: Vec<Segment>,
+    /// Detected (or requested) language
+    pub language: Option<String>,
+    /// Name of the backend that produced the transcript
+    pub backend: String,
+    /// Model used by the backend
+    pub model: Option<String>,
+    /// Response as returned by the backend, for subtitle formats
+    pub raw: Option<String>,
+    /// Marker starting each speaker turn in the text, see
+    /// [`with_speaker_markers`](Self::with_speaker_markers)
+    pub speaker_marker: Option<String>,
+}
+
+impl Transcript {
+    /// Create a transcript with only text.
+    pub fn new(text: impl Into<String>, backend: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            segments: Vec::new(),
+            language: None,
+            backend: backend.into(),
+            model: None,
+            raw: None,
+            speaker_marker: None,
+        }
+    }
+
+    /// Set the timed segments.
+    pub fn with_segments(mut self, segments: Vec<Segment>) -> Self {
+        self.segments = segments;
+        self
+    }
+
+    /// Set the language.
+    pub fn with_language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+
+    /// Set the model.
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// Set the raw backend response.
+    pub fn with_raw(mut self, raw: impl Into<String>) -> Self {
+        self.raw = Some(raw.into());
+        self
+    }
+
+    /// Whether the transcript contains no text.
+    pub fn is_empty(&self) -> bool {
+        self.text.trim().is_empty()
+    }
+
+    /// End time of the last segment.
+    pub fn duration(&self) -> Option<Duration> {
+        self.segments.last().map(|s| s.end)
+    }
+
+    /// Average log-probability over all segments that report one, weighted
+    /// by segment duration.
+    pub fn avg_logprob(&self) -> Option<f32> {
+        let (sum, weight) = self
+            .segments
+            .iter()
+            .filter_map(|s| Some((s.avg_logprob?, s.duration().as_secs_f32().max(f32::EPSILON))))
+            .fold((0.0, 0.0), |(sum, weight), (logprob, secs)| {
+                (sum + logprob * secs, weight + secs)
+            });
+        (weight > 0.0).then(|| sum / weight)
+    }
+
+    /// Iterate over the word timings of all segments.
+    pub fn words(&self) -> impl Iterator<Item = &Word> {
+        self.segments.iter().flat_map(|s| &s.words)
+    }
+
+    /// Group the segments into speaker turns, see [`Segment::speaker_turn`].
+    pub fn turns(&self) -> impl Iterator<Item = &[Segment]> {
+        self.segments
+            .split_inclusive(|s| s.speaker_turn)
+            .filter(|turn| !turn.is_empty())
+    }
+
+    /// Rebuild the text with one line per speaker turn, each starting with
+    /// `marker`.
+    ///
+    /// `{speaker}` in the marker alternates between 1 and 2, assuming a
+    /// conversation between two people. The text is left alone if there
+    /// is only one turn.
+    pub fn with_speaker_markers(mut self, marker: &str) -> Self {
+        self.speaker_marker = Some(marker.to_string());
+        if self.turns().nth(1).is_some() {
+            self.rebuild_text();
+        }
+        self
+    }
+
+    /// Set the text from the segments, after they changed.
+    ///
+    /// Keeps the speaker markers, if any.
+    pub fn rebuild_text(&mut self) {
+        let marker = match &self.speaker_marker {
+            Some(marker) if self.turns().nth(1).is_some() => marker,
+            _ => {
+                self.text = join_segments(&self.segments);
+                return;
+            }
+        };
+        self.text = self
+            .turns()
+            .enumerate()
+            .map(|(i, turn)| {
+                let marker = marker.replace("{speaker}", &(i % 2 + 1).to_string());
+                format!("{marker}{}", join_segments(turn))
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+}
+
+/// Join the text of segments, with a space between them unless it is
+/// already there.
+fn join_segments(segments: &[Segment]) -> String {
+    let mut text = String::new();
+    for segment in segments {
+        if !text.is_empty()
+            && !text.ends_with(char::is_whitespace)
+            && !segment.text.starts_with(char::is_whitespace)
+        {
+            text.push(' ');
+        }
+        text.push_str(&segment.text);
+    }
+    text.trim().to_string()
+}
+
+/// A timed piece of a transcript.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    /// Text of the segment
+    pub text: String,
+    /// Start time, relative to the start of the audio
+    pub start: Duration,
+    /// End time, relative to the start of the audio
+    pub end: Duration,
+    /// Word timings, empty if the backend doesn't provide them
+    pub words: Vec<Word>,
+    /// Average log-probability of the segment's tokens
+    pub avg_logprob: Option<f32>,
+    /// Probability that the segment contains no speech
+    pub no_speech_prob: Option<f32>,
+    /// Whether the speaker changes after this segment, only detected by
+    /// tinydiarize models
+    pub speaker_turn: bool,
+}
+
+impl Segment {
+    /// Create a segment with text and timestamps.
+    pub fn new(text: impl Into<String>, start: Duration, end: Duration) -> Self {
+        Self {
+            text: text.into(),
+            start,
+            end,
+            words: Vec::new(),
+            avg_logprob: None,
+            no_speech_prob: None,
+            speaker_turn: false,
+        }
+    }
+
+    /// Length of the segment.
+    pub fn duration(&self) -> Duration {
+        self.end.saturating_sub(self.start)
+    }
+}
+
+/// A timed word.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Word {
+    /// The word, without surrounding whitespace
+    pub text: String,
+    /// Start time, relative to the start of the audio
+    pub start: Duration,
+    /// End time, relative to the start of the audio
+    pub end: Duration,
+    /// Probability of the word, if known
+    pub probability: Option<f32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(s: f32) -> Duration {
+        Duration::from_secs_f32(s)
+    }
+
+    #[test]
+    fn test_avg_logprob_weighted_by_duration() {
+        let mut short = Segment::new("a", secs(0.0), secs(1.0));
+        short.avg_logprob = Some(-1.0);
+        let mut long = Segment::new("b", secs(1.0), secs(4.0));
+        long.avg_logprob = Some(-0.2);
+        let unknown = Segment::new("c", secs(4.0), secs(9.0));
+
+        let transcript = Transcript::new("a b c", "test").with_segments(vec![short, long, unknown]);
+
+        assert!((transcript.avg_logprob().unwrap() - -0.4).abs() < 1e-6);
+        assert_eq!(transcript.duration(), Some(secs(9.0)));
+    }
+
+    #[test]
+    fn test_text_only_transcript() {
+        let transcript = Transcript::new("  ", "test");
+
+        assert!(transcript.is_empty());
+        assert!(transcript.avg_logprob().is_none());
+        assert!(transcript.duration().is_none());
+        assert_eq!(transcript.words().count(), 0);
+    }
+
+    #[test]
+    fn test_speaker_markers() {
+        let mut question = Segment::new(" How are you?", secs(0.0), secs(1.0));
+        question.speaker_turn = true;
+        let answer = Segment::new(" Fine,", secs(1.0), secs(2.0));
+        let more = Segment::new(" thanks.", secs(2.0), secs(3.0));
+        let transcript = Transcript::new("How are you? Fine, thanks.", "test")
+            .with_segments(vec![question, answer, more]);
+
+        assert_eq!(
+            transcript.turns().map(<[_]>::len).collect::<Vec<_>>(),
+            [1, 2]
+        );
+        assert_eq!(
+            transcript
+                .clone()
+                .with_speaker_markers("[Speaker {speaker}] ")
+                .text,
+            "[Speaker 1] How are you?\n[Speaker 2] Fine, thanks."
+        );
+
+        let single = Transcript::new("Fine, thanks.", "test")
+            .with_segments(transcript.segments[1..].to_vec());
+        assert_eq!(single.with_speaker_markers("— ").text, "Fine, thanks.");
+    }
+}
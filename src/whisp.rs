@@ -1,8 +1,8 @@
 //! Whisp - Unobtrusive global speech-to-text.
 
 use std::sync::{Arc, RwLock, mpsc};
-use std::thread;
 use std::thread::sleep;
+use std::{fs, thread};
 
 use anyhow::{Context, Result};
 use arboard::Clipboard;
@@ -19,15 +19,20 @@ use tray_icon::menu::{AboutMetadataBuilder, Menu, MenuEvent, MenuItem, Predefine
 use tray_icon::{TrayIconBuilder, TrayIconEvent};
 use whisp::config_ext::ConfigExt;
 use whisp::event::WhispEvent;
-use whisp::icon::MicStateIcon;
+use whisp::icon::{MicStateIcon, loading_icon};
 use whisp::notify::NotificationLayer;
 use whisp::process::{AudioPipeline, SubmitResult};
+use whisp::transcribe::language;
 use whisp::{
-    AudioEvent, ConfigManager, DEFAULT_LOG_LEVEL, MicState, OpenAIClient, OpenAIConfig, Recorder,
-    RecordingHandle, Transcriber, TranscriptionBackend, VERSION,
+    AudioEvent, AutoGainConfig, Config, ConfigManager, DEFAULT_LOG_LEVEL, MicState, ModelEvent,
+    OpenAIClient, OpenAIConfig, Recorder, Recording, RecordingHandle, SpillConfig, Transcriber,
+    TranscriptionBackend, VERSION, journal_dir, orphaned_journals,
 };
 #[cfg(feature = "local-whisper")]
-use whisp::{LocalWhisperClient, LocalWhisperConfig, WhisperModel, ensure_model};
+use whisp::{
+    DecodingParams, LocalWhisperClient, LocalWhisperConfig, WhisperModel, ensure_model,
+    verify_model_file,
+};
 
 fn main() -> Result<()> {
     // Initialize the logger
@@ -52,9 +57,34 @@ fn main() -> Result<()> {
     hotkey_manager
         .register(hotkey)
         .context("Failed to register hotkey")?;
+    let pause_hotkey = config.pause_hotkey();
+    if let Err(e) = hotkey_manager.register(pause_hotkey) {
+        // Recording works without pausing, don't give it up over this
+        warn!(
+            "Failed to register pause hotkey, pausing is unavailable: {}",
+            e
+        );
+    }
+    let cancel_hotkey = config.cancel_hotkey();
+    if let Err(e) = hotkey_manager.register(cancel_hotkey) {
+        warn!(
+            "Failed to register cancel hotkey, use 'Cancel transcription' in the tray menu: {}",
+            e
+        );
+    }
 
     // Set up recorder
-    let recorder = Recorder::new();
+    let recorder = {
+        let config = config.read().unwrap();
+        let mut recorder = Recorder::new().with_spill(SpillConfig::from_config(&config)?);
+        if config.journal {
+            recorder = recorder.with_journal(journal_dir()?);
+        }
+        match AutoGainConfig::from_config(&config) {
+            Some(agc) => recorder.with_auto_gain(agc),
+            None => recorder,
+        }
+    };
     let mut active_recording: Option<RecordingHandle> = None;
 
     // Set up keyboard and clipboard interaction
@@ -65,9 +95,12 @@ fn main() -> Result<()> {
     let tray_menu = Menu::new();
     let icon_quit = MenuItem::new("Quit", true, None);
     let icon_copy_config = MenuItem::new("Copy config path", true, None);
+    let icon_cancel = MenuItem::new("Cancel transcription", true, None);
     tray_menu.append_items(&[
         &MenuItem::new("Whisp", false, None),
         &PredefinedMenuItem::separator(),
+        &icon_cancel,
+        &PredefinedMenuItem::separator(),
         &PredefinedMenuItem::about(
             None,
             Some(
@@ -81,6 +114,29 @@ fn main() -> Result<()> {
         &icon_quit,
     ])?;
 
+    // Offer to recover recordings that were interrupted by a crash
+    let mut orphaned = orphaned_journals(&journal_dir()?).unwrap_or_else(|e| {
+        warn!("Failed to look for unfinished recordings: {:#}", e);
+        Vec::new()
+    });
+    let icon_recover = MenuItem::new(
+        format!("Transcribe unfinished recordings ({})", orphaned.len()),
+        true,
+        None,
+    );
+    let icon_discard_orphaned = MenuItem::new("Discard unfinished recordings", true, None);
+    let orphaned_separator = PredefinedMenuItem::separator();
+    if !orphaned.is_empty() {
+        tray_menu.insert_items(
+            &[&icon_recover, &icon_discard_orphaned, &orphaned_separator],
+            2,
+        )?;
+        warn!(
+            "Found {} unfinished recording(s), transcribe or discard them from the tray menu",
+            orphaned.len()
+        );
+    }
+
     // Set up the event loop
     let mut icon_tray = None;
 
@@ -108,81 +164,56 @@ fn main() -> Result<()> {
         }
     });
 
+    // Bridge model loading and unloading to the tao event loop
+    let (model_event_tx, model_event_rx) = mpsc::channel::<ModelEvent>();
+    let event_sender_clone = event_sender.clone();
+    thread::spawn(move || {
+        while let Ok(model_event) = model_event_rx.recv() {
+            let event = match model_event {
+                ModelEvent::Loading => WhispEvent::ModelLoading,
+                ModelEvent::Loaded => WhispEvent::ModelLoaded,
+                ModelEvent::Unloaded => WhispEvent::ModelUnloaded,
+            };
+            event_sender_clone.send_event(event).ok();
+        }
+    });
+
     // Track hotkey state for paste timing (avoid paste while hotkey held)
     let mut hotkey_held = false;
     let mut pending_paste: Option<(String, Option<String>)> = None;
 
-    // Create transcriber based on config
-    let transcriber: Arc<dyn Transcriber> = {
+    // Create a transcriber for each backend, chained by the pipeline
+    let backends = {
         let cfg = config.read().unwrap();
-        match cfg.backend() {
-            TranscriptionBackend::OpenAI => {
-                let api_key = cfg
-                    .key_openai()
-                    .context("OpenAI API key not configured")?
-                    .to_string();
-                let mut openai_config = OpenAIConfig::new(api_key);
-                if let Some(model) = cfg.model() {
-                    openai_config = openai_config.with_model(model);
-                }
-                Arc::new(OpenAIClient::new(openai_config))
-            }
-            #[cfg(feature = "local-whisper")]
-            TranscriptionBackend::Local => {
-                // Parse model name from config, or use default
-                let model = match cfg.local_model() {
-                    Some(name) => WhisperModel::from_name(name).ok_or_else(|| {
-                        anyhow::anyhow!(
-                            "Unknown model '{}'. Available models:\n  {}\n\
-                             See https://huggingface.co/ggerganov/whisper.cpp for details.",
-                            name,
-                            WhisperModel::all_names().join("\n  ")
-                        )
-                    })?,
-                    None => WhisperModel::default(),
-                };
-
-                info!(model = ?model, "Using local Whisper backend");
-
-                // Ensure model is downloaded before continuing
-                let rt = tokio::runtime::Runtime::new()
-                    .context("Failed to create tokio runtime for model download")?;
-
-                rt.block_on(async {
-                    use std::sync::atomic::{AtomicU32, Ordering};
-                    let last_milestone = AtomicU32::new(u32::MAX); // u32::MAX = not set
-                    ensure_model(model, move |downloaded, total| {
-                        let percent = (downloaded as f64 / total as f64 * 100.0) as u32;
-                        let milestone = percent / 25 * 25; // Round down to nearest 25
-                        let prev = last_milestone.swap(milestone, Ordering::Relaxed);
-                        if prev != milestone {
-                            info!(
-                                downloaded_mb = downloaded / 1_000_000,
-                                total_mb = total / 1_000_000,
-                                percent = milestone,
-                                "Downloading model"
-                            );
-                        }
-                    })
-                    .await
-                })
-                .context("Failed to download Whisper model")?;
-
-                let local_config = LocalWhisperConfig::new(model).with_coreml(cfg.coreml());
-                Arc::new(LocalWhisperClient::new(local_config))
-            }
-            #[cfg(not(feature = "local-whisper"))]
-            TranscriptionBackend::Local => {
-                anyhow::bail!(
-                    "Local whisper backend requested but not compiled in. \
-                     Rebuild with --features local-whisper"
-                );
-            }
+        if let Some(unknown) = cfg
+            .languages()
+            .iter()
+            .find(|code| !language::is_supported(code))
+        {
+            anyhow::bail!("Unknown language '{unknown}' in `languages`");
         }
+        cfg.backends()
+            .iter()
+            .map(|backend| create_transcriber(backend, &cfg, &model_event_tx))
+            .collect::<Result<Vec<_>>>()?
     };
 
     // Set up processor for handling audio data async operations
-    let audio_pipeline = AudioPipeline::new(config.clone(), transcriber, event_sender.clone())?;
+    let audio_pipeline = AudioPipeline::new(config.clone(), backends, event_sender.clone())?;
+
+    // Load local models now rather than on the first dictation
+    if config
+        .read()
+        .unwrap()
+        .backends()
+        .contains(&TranscriptionBackend::Local)
+    {
+        audio_pipeline.warm_up();
+    }
+
+    // The tray icon shows the model loading unless recording
+    let mut mic_state = MicState::Idle;
+    let mut model_loading = false;
 
     event_loop.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Wait;
@@ -213,6 +244,43 @@ fn main() -> Result<()> {
             if event.id == icon_quit.id() {
                 icon_tray.take();
                 *control_flow = ControlFlow::Exit;
+            } else if event.id == icon_recover.id() || event.id == icon_discard_orphaned.id() {
+                let recover = event.id == icon_recover.id();
+                for path in orphaned.drain(..) {
+                    if !recover {
+                        if let Err(e) = fs::remove_file(&path) {
+                            warn!(path = ?path, "Failed to discard unfinished recording: {}", e);
+                        }
+                        continue;
+                    }
+
+                    let submitted = Recording::from_journal(&path)
+                        .map_err(anyhow::Error::from)
+                        .and_then(|recording| audio_pipeline.submit(recording));
+                    match submitted {
+                        Ok(SubmitResult::Sent(_)) => {
+                            event_sender
+                                .send_event(WhispEvent::StateChanged(MicState::Processing))
+                                .ok();
+                        }
+                        Ok(SubmitResult::Discarded) => {}
+                        Ok(SubmitResult::NoSpeech(speech)) => {
+                            warn!(
+                                path = ?path,
+                                speech = ?speech,
+                                "Recovered recording holds no speech, discarded"
+                            );
+                        }
+                        Err(e) => {
+                            error!(path = ?path, "Failed to recover recording: {:?}", e);
+                        }
+                    }
+                }
+                tray_menu.remove(&icon_recover).ok();
+                tray_menu.remove(&icon_discard_orphaned).ok();
+                tray_menu.remove(&orphaned_separator).ok();
+            } else if event.id == icon_cancel.id() {
+                audio_pipeline.cancel_all();
             } else if event.id == icon_copy_config.id()
                 && let Err(e) =
                     clipboard.set_text(config_manager.config_path().to_string_lossy().into_owned())
@@ -231,14 +299,36 @@ fn main() -> Result<()> {
             match event {
                 WhispEvent::StateChanged(state) => {
                     info!(state = ?state, "State changed");
-                    icon_tray.as_ref().map(|i| i.set_icon(Some(state.icon())));
+                    mic_state = state;
+                    let icon = if model_loading && shows_loading(state) {
+                        loading_icon()
+                    } else {
+                        state.icon()
+                    };
+                    icon_tray.as_ref().map(|i| i.set_icon(Some(icon)));
+                }
+                WhispEvent::ModelLoading => {
+                    model_loading = true;
+                    if shows_loading(mic_state) {
+                        icon_tray.as_ref().map(|i| i.set_icon(Some(loading_icon())));
+                    }
                 }
-                WhispEvent::TranscriptReady(text) => {
+                WhispEvent::ModelLoaded => {
+                    model_loading = false;
+                    icon_tray
+                        .as_ref()
+                        .map(|i| i.set_icon(Some(mic_state.icon())));
+                }
+                WhispEvent::ModelUnloaded => {
+                    info!("Model unloaded, the next dictation loads it again");
+                }
+                WhispEvent::TranscriptReady(transcript) => {
                     if active_recording.is_none() {
                         event_sender
                             .send_event(WhispEvent::StateChanged(MicState::Idle))
                             .ok();
                     }
+                    let text = transcript.text;
 
                     let config = config.read().unwrap();
                     info!(
@@ -288,6 +378,14 @@ fn main() -> Result<()> {
                     }
                     warn!(bytes = data.len(), "Transcription failed after retries");
                 }
+                WhispEvent::TranscriptionCancelled
+                | WhispEvent::TranscriptFiltered => {
+                    if active_recording.is_none() {
+                        event_sender
+                            .send_event(WhispEvent::StateChanged(MicState::Idle))
+                            .ok();
+                    }
+                }
                 WhispEvent::AudioError(_) => {
                     warn!("Audio processing error received");
                 }
@@ -295,7 +393,42 @@ fn main() -> Result<()> {
         }
 
         // Handle hotkey events
-        if let Ok(event) = hotkey_channel.try_recv()
+        let hotkey_event = hotkey_channel.try_recv().ok();
+        if let Some(event) = &hotkey_event
+            && event.id() == pause_hotkey.id()
+            && event.state() == HotKeyState::Pressed
+            && let Some(recording) = &active_recording
+        {
+            let mic_state = if recording.is_paused() {
+                recording.resume();
+                MicState::Active
+            } else {
+                recording.pause();
+                MicState::Paused
+            };
+            event_sender
+                .send_event(WhispEvent::StateChanged(mic_state))
+                .ok();
+        }
+        if let Some(event) = &hotkey_event
+            && event.id() == cancel_hotkey.id()
+            && event.state() == HotKeyState::Pressed
+        {
+            // Drop the recording in progress, if any, and everything in flight
+            if let Some(mut recording) = active_recording.take() {
+                match recording.finish() {
+                    Ok(Some(recording)) => recording.discard(),
+                    Ok(None) => {}
+                    Err(e) => error!(error = ?e, "Failed to finish cancelled recording"),
+                }
+                info!("Recording cancelled");
+            }
+            audio_pipeline.cancel_all();
+            event_sender
+                .send_event(WhispEvent::StateChanged(MicState::Idle))
+                .ok();
+        }
+        if let Some(event) = hotkey_event
             && event.id() == hotkey.id()
         {
             match event.state() {
@@ -305,7 +438,11 @@ fn main() -> Result<()> {
                         Some(mut recording) => match recording.finish() {
                             Ok(Some(data)) => match audio_pipeline.submit(data) {
                                 Ok(SubmitResult::Discarded) => MicState::Idle,
-                                Ok(SubmitResult::Sent) => MicState::Processing,
+                                Ok(SubmitResult::NoSpeech(speech)) => {
+                                    warn!(speech = ?speech, "No speech detected, recording discarded");
+                                    MicState::Idle
+                                }
+                                Ok(SubmitResult::Sent(_)) => MicState::Processing,
                                 Err(e) => {
                                     error!("Failed to submit audio to processor: {:?}", e);
                                     MicState::Idle
@@ -354,6 +491,108 @@ fn main() -> Result<()> {
     });
 }
 
+/// Whether the tray icon shows the model loading in `state`, rather than
+/// the state itself.
+fn shows_loading(state: MicState) -> bool {
+    matches!(state, MicState::Idle | MicState::Processing)
+}
+
+/// Create the transcriber for one backend.
+///
+/// Backends that load a model report it to `model_events`.
+fn create_transcriber(
+    backend: &TranscriptionBackend,
+    cfg: &Config,
+    #[cfg_attr(not(feature = "local-whisper"), allow(unused_variables))]
+    model_events: &mpsc::Sender<ModelEvent>,
+) -> Result<Arc<dyn Transcriber>> {
+    let transcriber: Arc<dyn Transcriber> = match backend {
+        TranscriptionBackend::OpenAI => {
+            let openai_config =
+                OpenAIConfig::from_config(cfg).context("OpenAI API key not configured")?;
+            if cfg.languages().len() > 1 && !openai_config.reports_language() {
+                warn!(
+                    "OpenAI model {} doesn't report the language it detects, `languages` \
+                     is only a hint to it",
+                    openai_config.model()
+                );
+            }
+            Arc::new(OpenAIClient::new(openai_config))
+        }
+        #[cfg(feature = "local-whisper")]
+        TranscriptionBackend::Local => {
+            // Parse model name from config, or use default
+            let model = match cfg.local_model() {
+                Some(name) => WhisperModel::from_name(name).ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Unknown model '{}'. Available models:\n  {}\n\
+                         See https://huggingface.co/ggerganov/whisper.cpp for details.",
+                        name,
+                        WhisperModel::all_names().join("\n  ")
+                    )
+                })?,
+                None => WhisperModel::default(),
+            };
+            let decoding = DecodingParams::from_config(cfg);
+            decoding
+                .validate()
+                .context("Invalid local whisper decoding settings")?;
+
+            let mut local_config = LocalWhisperConfig::new(model)
+                .with_coreml(cfg.coreml())
+                .with_decoding(decoding)
+                .with_unload_after_idle(cfg.unload_after_idle())
+                .with_pool_size(cfg.local_pool_size)
+                .with_speaker_marker(cfg.speaker_marker())
+                .with_model_events(model_events.clone());
+
+            if let Some(path) = &cfg.local_model_path {
+                // Check the file now, rather than on the first dictation
+                let header = verify_model_file(path, cfg.local_model_sha1.as_deref())
+                    .context("Custom Whisper model can't be used")?;
+                info!(path = ?path, header = ?header, "Using custom local Whisper model");
+                local_config = local_config.with_model_path(path.clone());
+            } else {
+                info!(model = ?model, "Using local Whisper backend");
+
+                // Ensure model is downloaded before continuing
+                let rt = tokio::runtime::Runtime::new()
+                    .context("Failed to create tokio runtime for model download")?;
+
+                rt.block_on(async {
+                    use std::sync::atomic::{AtomicU32, Ordering};
+                    let last_milestone = AtomicU32::new(u32::MAX); // u32::MAX = not set
+                    ensure_model(model, move |downloaded, total| {
+                        let percent = (downloaded as f64 / total as f64 * 100.0) as u32;
+                        let milestone = percent / 25 * 25; // Round down to nearest 25
+                        let prev = last_milestone.swap(milestone, Ordering::Relaxed);
+                        if prev != milestone {
+                            info!(
+                                downloaded_mb = downloaded / 1_000_000,
+                                total_mb = total / 1_000_000,
+                                percent = milestone,
+                                "Downloading model"
+                            );
+                        }
+                    })
+                    .await
+                })
+                .context("Failed to download Whisper model")?;
+            }
+
+            Arc::new(LocalWhisperClient::new(local_config))
+        }
+        #[cfg(not(feature = "local-whisper"))]
+        TranscriptionBackend::Local => {
+            anyhow::bail!(
+                "Local whisper backend requested but not compiled in. \
+                 Rebuild with --features local-whisper"
+            );
+        }
+    };
+    Ok(transcriber)
+}
+
 fn paste(enigo: &mut Enigo, hotkey: &HotKey) -> anyhow::Result<()> {
     use enigo::Direction::{Click, Press, Release};
     use enigo::{Key, Keyboard};